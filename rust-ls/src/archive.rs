@@ -0,0 +1,340 @@
+use std::{
+    collections::BTreeMap,
+    ffi::{OsStr, OsString},
+    fs::{self, File},
+    io::{self, BufReader, Read},
+    path::{Component, Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use chrono::{Local, TimeZone};
+use flate2::read::GzDecoder;
+use tar::EntryType;
+use zip::ZipArchive;
+
+use crate::{
+    content,
+    entry::{Entry, FileKind, LinkState, LinkedFile},
+};
+
+/// How many symbolic links are followed before a chain is taken for a loop, like Linux does.
+const MAX_LINKS: usize = 40;
+
+/// Archive formats whose contents can be listed like a directory.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Tar,
+    /// a tar archive compressed with gzip (`.tar.gz`, `.tgz`)
+    TarGzip,
+    Zip,
+}
+
+impl ArchiveFormat {
+    /// Recognizes an archive by the magic bytes at its start, like `--mime` does, so that it
+    /// does not matter what the file is called. Any gzip file is taken for a compressed tar
+    /// archive; reading it fails if it is not one.
+    pub fn of(path: &Path) -> Option<Self> {
+        let metadata = fs::metadata(path).ok()?;
+        if !metadata.is_file() {
+            return None;
+        }
+        match content::mime_type(path, FileKind::File, metadata.len())? {
+            "application/x-tar" => Some(ArchiveFormat::Tar),
+            "application/gzip" => Some(ArchiveFormat::TarGzip),
+            "application/zip" => Some(ArchiveFormat::Zip),
+            _ => None,
+        }
+    }
+}
+
+/// The files in an archive, read from its headers without extracting anything.
+///
+/// Members are kept as [`Entry`] values whose path is the archive's path followed by their path
+/// inside it (e.g. `release.zip/bin/tool`), so that they are sorted and formatted like files on
+/// disk. Directories that the archive only implies, by holding files below them, are added.
+#[derive(Debug)]
+pub struct Archive {
+    path: PathBuf,
+    /// the archive's own metadata, which members start from
+    metadata: fs::Metadata,
+    /// path inside the archive → member
+    members: BTreeMap<PathBuf, Entry>,
+}
+
+impl Archive {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let format = ArchiveFormat::of(path).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidData, "not a tar, tar.gz or zip archive")
+        })?;
+        let file = File::open(path)?;
+        let metadata = file.metadata()?;
+        let mut archive = Archive { path: path.to_path_buf(), metadata, members: BTreeMap::new() };
+        match format {
+            ArchiveFormat::Tar => archive.read_tar(BufReader::new(file))?,
+            ArchiveFormat::TarGzip => archive.read_tar(GzDecoder::new(BufReader::new(file)))?,
+            ArchiveFormat::Zip => archive.read_zip(file)?,
+        }
+        archive.add_implied_directories();
+        archive.resolve_links();
+        Ok(archive)
+    }
+
+    /// The archive file itself.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The member at `path` inside the archive, if there is one.
+    pub fn member(&self, path: &Path) -> Option<&Entry> {
+        self.members.get(&normalize(path))
+    }
+
+    /// Whether `path` inside the archive is a directory. The empty path, the archive itself,
+    /// always is.
+    pub fn is_dir(&self, path: &Path) -> bool {
+        let path = normalize(path);
+        path.as_os_str().is_empty() || self.members.get(&path).is_some_and(Entry::is_dir)
+    }
+
+    /// The members directly inside the directory `dir`, in the order of their paths.
+    pub fn read_dir(&self, dir: &Path) -> io::Result<Vec<Entry>> {
+        if !self.is_dir(dir) {
+            let errno = match self.member(dir) {
+                Some(_) => libc::ENOTDIR,
+                None => libc::ENOENT,
+            };
+            return Err(io::Error::from_raw_os_error(errno));
+        }
+        let dir = normalize(dir);
+        let entries = self
+            .members
+            .iter()
+            .filter(|(path, _)| path.parent() == Some(dir.as_path()))
+            .map(|(_, entry)| entry.clone())
+            .collect();
+        Ok(entries)
+    }
+
+    fn read_tar(&mut self, reader: impl Read) -> io::Result<()> {
+        let mut archive = tar::Archive::new(reader);
+        for member in archive.entries()? {
+            let member = member?;
+            let header = member.header();
+            let kind = match header.entry_type() {
+                EntryType::Directory => FileKind::Directory,
+                EntryType::Symlink => FileKind::Symlink,
+                EntryType::Char => FileKind::CharDevice,
+                EntryType::Block => FileKind::BlockDevice,
+                EntryType::Fifo => FileKind::Fifo,
+                // GNU and pax extension records are folded into the members they describe
+                // by the tar crate; hard links are files like any other
+                _ => FileKind::File,
+            };
+            let rdev = match (header.device_major(), header.device_minor()) {
+                (Ok(Some(major)), Ok(Some(minor))) if kind.is_device() => {
+                    libc::makedev(major, minor)
+                },
+                _ => 0,
+            };
+            let link_target = match kind {
+                FileKind::Symlink => member.link_name()?.map(|target| target.into_owned()),
+                _ => None,
+            };
+
+            let path = member.path()?.into_owned();
+            let Some(mut entry) = self.new_member(&path, kind) else {
+                continue;
+            };
+            entry.mode = header.mode()? & 0o7777;
+            entry.uid = header.uid()? as u32;
+            entry.gid = header.gid()? as u32;
+            entry.user = header.username().ok().flatten().map(str::to_string);
+            entry.group = header.groupname().ok().flatten().map(str::to_string);
+            entry.size = member.size();
+            entry.rdev = rdev;
+            entry.modified =
+                SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(header.mtime()?));
+            entry.link_target = link_target;
+            self.insert(entry);
+        }
+        Ok(())
+    }
+
+    fn read_zip(&mut self, file: File) -> io::Result<()> {
+        let mut archive = ZipArchive::new(BufReader::new(file)).map_err(io::Error::other)?;
+        for index in 0..archive.len() {
+            let member = archive.by_index_raw(index).map_err(io::Error::other)?;
+            let kind = if member.is_dir() {
+                FileKind::Directory
+            } else if member.is_symlink() {
+                FileKind::Symlink
+            } else {
+                FileKind::File
+            };
+            let Some(mut entry) = self.new_member(Path::new(member.name()), kind) else {
+                continue;
+            };
+            entry.mode = match member.unix_mode() {
+                Some(mode) => mode & 0o7777,
+                // written on a system without permissions, e.g. Windows
+                None if kind == FileKind::Directory => 0o755,
+                None => 0o644,
+            };
+            entry.size = member.size();
+            // DOS timestamps are in local time, to the even second
+            entry.modified = member.last_modified().and_then(|time| {
+                let time = Local
+                    .with_ymd_and_hms(
+                        time.year().into(),
+                        time.month().into(),
+                        time.day().into(),
+                        time.hour().into(),
+                        time.minute().into(),
+                        time.second().into(),
+                    )
+                    .earliest()?;
+                Some(SystemTime::from(time))
+            });
+            drop(member);
+            if kind == FileKind::Symlink {
+                // the target is the contents of the link, which may be compressed
+                let mut target = String::new();
+                entry.link_target = archive
+                    .by_index(index)
+                    .ok()
+                    .and_then(|mut link| link.read_to_string(&mut target).ok())
+                    .map(|_| PathBuf::from(target));
+            }
+            self.insert(entry);
+        }
+        Ok(())
+    }
+
+    /// An entry for the member at `path`, to be filled in from its header. Leading `/` and
+    /// `./`, and any `..`, are dropped, like extracting the archive would; `None` for a member
+    /// that names nothing but the archive's root.
+    ///
+    /// Members start out owned by whoever owns the archive, who would own them once extracted;
+    /// zip records no owner to replace that with.
+    fn new_member(&self, path: &Path, kind: FileKind) -> Option<Entry> {
+        let path = normalize(path);
+        let name = OsString::from(path.file_name()?);
+        let mut entry = Entry::from_metadata(name, self.path.join(&path), &self.metadata);
+        entry.kind = kind;
+        entry.mode = 0o644;
+        entry.nlink = 1;
+        entry.size = 0;
+        // nothing of a member is allocated on disk, and it has no inode of its own
+        entry.blocks = 0;
+        entry.ino = 0;
+        entry.dev = 0;
+        entry.accessed = None;
+        entry.modified = None;
+        entry.changed = None;
+        entry.created = None;
+        Some(entry)
+    }
+
+    fn insert(&mut self, entry: Entry) {
+        let path = entry
+            .path
+            .strip_prefix(&self.path)
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        self.members.insert(path, entry);
+    }
+
+    /// Adds the directories that hold members without being members themselves.
+    fn add_implied_directories(&mut self) {
+        let mut implied = Vec::new();
+        for path in self.members.keys() {
+            for dir in path.ancestors().skip(1) {
+                if dir.as_os_str().is_empty() || self.members.contains_key(dir) {
+                    break;
+                }
+                implied.push(dir.to_path_buf());
+            }
+        }
+        for dir in implied {
+            if let Some(mut entry) = self.new_member(&dir, FileKind::Directory) {
+                entry.mode = 0o755;
+                self.insert(entry);
+            }
+        }
+    }
+
+    /// Follows every symbolic link among the members, so that links are told apart as broken
+    /// like they would be once the archive is extracted.
+    fn resolve_links(&mut self) {
+        let links: Vec<_> = self
+            .members
+            .iter()
+            .filter(|(_, entry)| entry.kind == FileKind::Symlink)
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in links {
+            let (state, linked_file) = self.follow(&path);
+            if let Some(entry) = self.members.get_mut(&path) {
+                entry.link_state = Some(state);
+                entry.linked_file = linked_file;
+            }
+        }
+    }
+
+    /// Resolves `path` inside the archive one component at a time, following symbolic links on
+    /// the way. Absolute targets start from the root of the archive, where they would lead if
+    /// it were extracted at `/`.
+    fn follow(&self, path: &Path) -> (LinkState, Option<LinkedFile>) {
+        // components still to resolve, the next one last
+        let mut pending: Vec<_> = path.iter().rev().map(OsStr::to_os_string).collect();
+        let mut resolved = PathBuf::new();
+        let mut links = 0;
+        while let Some(component) = pending.pop() {
+            if component == ".." {
+                resolved.pop();
+                continue;
+            }
+            if component == "." || component == "/" {
+                continue;
+            }
+            let candidate = resolved.join(&component);
+            let Some(member) = self.members.get(&candidate) else {
+                return (LinkState::Broken, None);
+            };
+            if member.kind == FileKind::Symlink {
+                links += 1;
+                if links > MAX_LINKS {
+                    return (LinkState::Loop, None);
+                }
+                let Some(target) = &member.link_target else {
+                    return (LinkState::Broken, None);
+                };
+                if target.is_absolute() {
+                    resolved = PathBuf::new();
+                }
+                pending.extend(target.iter().rev().map(OsStr::to_os_string));
+                continue;
+            }
+            if !pending.is_empty() && !member.is_dir() {
+                return (LinkState::Broken, None);
+            }
+            resolved = candidate;
+        }
+
+        let linked_file = match self.members.get(&resolved) {
+            Some(member) => {
+                LinkedFile { kind: member.kind, mode: member.mode, nlink: member.nlink }
+            },
+            // the root of the archive
+            None => LinkedFile { kind: FileKind::Directory, mode: 0o755, nlink: 1 },
+        };
+        (LinkState::Resolved, Some(linked_file))
+    }
+}
+
+/// `path` inside an archive, with only its normal components.
+fn normalize(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|component| matches!(component, Component::Normal(_)))
+        .collect()
+}
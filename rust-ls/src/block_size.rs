@@ -0,0 +1,214 @@
+use std::{env, str::FromStr};
+
+use crate::format::SizeFormatter;
+
+/// Unit letters for powers of the base, starting at the base itself.
+const UNITS: [char; 8] = ['K', 'M', 'G', 'T', 'P', 'E', 'Z', 'Y'];
+
+/// The unit sizes and allocated blocks are shown in (`-h`, `--si`, `--block-size`).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BlockSize {
+    /// the largest fitting power of `base` (1024 for `-h`, 1000 for `--si`), e.g. `1.2K` or `34M`
+    Human { base: u64 },
+    /// whole multiples of `size` bytes, rounded up and followed by `suffix`
+    Fixed { size: u64, suffix: String },
+}
+
+impl FromStr for BlockSize {
+    type Err = String;
+
+    /// Parses a `--block-size` value like GNU `ls`: `human-readable`, `si`, or a size as read by
+    /// [`parse_size`]. A unit without a number is also printed after each size, so
+    /// `--block-size=M` shows `12M` where `--block-size=1M` shows `12`.
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        match spec {
+            "human-readable" => return Ok(BlockSize::Human { base: 1024 }),
+            "si" => return Ok(BlockSize::Human { base: 1000 }),
+            _ => {},
+        }
+
+        let size = parse_size(spec)
+            .ok()
+            .filter(|&size| size > 0)
+            .ok_or_else(|| format!("invalid block size '{}'", spec))?;
+        let unit = spec.trim_start_matches(|c: char| c.is_ascii_digit());
+        let suffix = if unit.len() == spec.len() { unit.to_string() } else { String::new() };
+        Ok(BlockSize::Fixed { size, suffix })
+    }
+}
+
+impl BlockSize {
+    /// Exact byte counts, the default for the size column.
+    pub fn bytes() -> Self {
+        BlockSize::Fixed { size: 1, suffix: String::new() }
+    }
+
+    /// 1024-byte blocks, the default for the `total` line and the `-s` column.
+    pub fn kibibytes() -> Self {
+        BlockSize::Fixed { size: 1024, suffix: String::new() }
+    }
+
+    /// The size in `$LS_BLOCK_SIZE`, then `$BLOCK_SIZE`, if one of them is set and valid.
+    pub fn from_env() -> Option<Self> {
+        ["LS_BLOCK_SIZE", "BLOCK_SIZE"]
+            .iter()
+            .find_map(|name| env::var(name).ok())
+            .and_then(|spec| spec.parse().ok())
+    }
+
+    /// `bytes` in this unit, without padding.
+    pub fn scale(&self, bytes: u64) -> String {
+        match self {
+            BlockSize::Human { base } => to_human_readable_size(bytes, *base),
+            BlockSize::Fixed { size, suffix } => format!("{}{}", bytes.div_ceil(*size), suffix),
+        }
+    }
+}
+
+impl SizeFormatter for BlockSize {
+    fn format_size(&self, size: u64) -> String {
+        match self {
+            BlockSize::Human { .. } => format!("{:>5}", self.scale(size)),
+            BlockSize::Fixed { .. } => format!("{:>8}", self.scale(size)),
+        }
+    }
+}
+
+/// Parses a byte count such as `512`, `10K`, `1MiB` or `2GB`: an optional number followed by an
+/// optional unit, where `K` and `KiB` are powers of 1024 and `KB` is a power of 1000.
+pub fn parse_size(spec: &str) -> Result<u64, String> {
+    let invalid = || format!("invalid size '{}'", spec);
+    let digits = spec.len() - spec.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let (number, unit) = spec.split_at(digits);
+    let number: u64 = if number.is_empty() { 1 } else { number.parse().map_err(|_| invalid())? };
+    let multiplier = if unit.is_empty() {
+        1
+    } else {
+        let mut chars = unit.chars();
+        let letter = chars.next().map(|c| c.to_ascii_uppercase());
+        let power = UNITS
+            .iter()
+            .position(|&u| Some(u) == letter)
+            .ok_or_else(invalid)?
+            + 1;
+        let base: u64 = match chars.as_str() {
+            "" | "iB" => 1024,
+            "B" => 1000,
+            _ => return Err(invalid()),
+        };
+        base.checked_pow(power as u32).ok_or_else(invalid)?
+    };
+    number.checked_mul(multiplier).ok_or_else(invalid)
+}
+
+/// Sizes like GNU `ls -h`: bytes below `base`, then one decimal place below 10 and whole numbers
+/// from there, always rounded up so a size is never understated. Powers of 1000 use a lowercase
+/// `k`, like `--si` does.
+pub fn to_human_readable_size(size: u64, base: u64) -> String {
+    if size < base {
+        return size.to_string();
+    }
+
+    let base_f = base as f64;
+    let mut value = size as f64;
+    let mut unit_index = 0;
+    while value >= base_f && unit_index < UNITS.len() {
+        value /= base_f;
+        unit_index += 1;
+    }
+
+    // rounding up may carry into the next digit or the next unit
+    let mut text = if value < 10.0 && (value * 10.0).ceil() < 100.0 {
+        format!("{:.1}", (value * 10.0).ceil() / 10.0)
+    } else {
+        value.ceil().to_string()
+    };
+    if value.ceil() >= base_f && unit_index < UNITS.len() {
+        text = "1.0".to_string();
+        unit_index += 1;
+    }
+
+    let unit = UNITS[unit_index - 1];
+    let unit = if base == 1000 && unit == 'K' { 'k' } else { unit };
+    format!("{}{}", text, unit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn human_readable_sizes_round_up() {
+        assert_eq!(to_human_readable_size(0, 1024), "0");
+        assert_eq!(to_human_readable_size(1023, 1024), "1023");
+        assert_eq!(to_human_readable_size(1024, 1024), "1.0K");
+        assert_eq!(to_human_readable_size(1025, 1024), "1.1K");
+        assert_eq!(to_human_readable_size(1536, 1024), "1.5K");
+        assert_eq!(to_human_readable_size(10 * 1024, 1024), "10K");
+        assert_eq!(to_human_readable_size(10 * 1024 + 1, 1024), "11K");
+        assert_eq!(to_human_readable_size(5 * 1024 * 1024 * 1024, 1024), "5.0G");
+    }
+
+    #[test]
+    fn human_readable_sizes_carry() {
+        // 9.99K rounds up to 10K rather than 10.0K
+        assert_eq!(to_human_readable_size(10 * 1024 - 1, 1024), "10K");
+        // 1023.99K rounds up into the next unit
+        assert_eq!(to_human_readable_size(1024 * 1024 - 1, 1024), "1.0M");
+        assert_eq!(to_human_readable_size(1024 * 1024, 1024), "1.0M");
+    }
+
+    #[test]
+    fn si_sizes() {
+        assert_eq!(to_human_readable_size(999, 1000), "999");
+        assert_eq!(to_human_readable_size(1000, 1000), "1.0k");
+        assert_eq!(to_human_readable_size(1500, 1000), "1.5k");
+        assert_eq!(to_human_readable_size(999_999, 1000), "1.0M");
+    }
+
+    #[test]
+    fn sizes_with_units() {
+        assert_eq!(parse_size("512"), Ok(512));
+        assert_eq!(parse_size("10K"), Ok(10 * 1024));
+        assert_eq!(parse_size("10k"), Ok(10 * 1024));
+        assert_eq!(parse_size("1MiB"), Ok(1024 * 1024));
+        assert_eq!(parse_size("2GB"), Ok(2_000_000_000));
+        assert_eq!(parse_size("K"), Ok(1024));
+        assert_eq!(parse_size("KB"), Ok(1000));
+    }
+
+    #[test]
+    fn invalid_sizes() {
+        assert!(parse_size("10X").is_err());
+        assert!(parse_size("1Kb").is_err());
+        assert!(parse_size("-1").is_err());
+        // 1024^8 does not fit in 64 bits
+        assert!(parse_size("1Y").is_err());
+        assert!(parse_size("20E").is_err());
+        assert!(parse_size("99999999999999999999").is_err());
+    }
+
+    #[test]
+    fn block_sizes() {
+        assert_eq!("human-readable".parse(), Ok(BlockSize::Human { base: 1024 }));
+        assert_eq!("si".parse(), Ok(BlockSize::Human { base: 1000 }));
+        assert_eq!("1M".parse(), Ok(BlockSize::Fixed { size: 1024 * 1024, suffix: String::new() }));
+        // a unit alone is shown after every size
+        assert_eq!(
+            "M".parse(),
+            Ok(BlockSize::Fixed { size: 1024 * 1024, suffix: "M".to_string() })
+        );
+        assert!("0".parse::<BlockSize>().is_err());
+        assert!("x".parse::<BlockSize>().is_err());
+    }
+
+    #[test]
+    fn fixed_block_sizes_round_up() {
+        let kibibytes = BlockSize::kibibytes();
+        assert_eq!(kibibytes.scale(0), "0");
+        assert_eq!(kibibytes.scale(1), "1");
+        assert_eq!(kibibytes.scale(1025), "2");
+        let megabytes: BlockSize = "M".parse().unwrap();
+        assert_eq!(megabytes.scale(3 * 1024 * 1024), "3M");
+    }
+}
@@ -0,0 +1,496 @@
+use std::{
+    ffi::{OsStr, OsString},
+    fs::{self, File},
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use clap::ValueEnum;
+use crossterm::{
+    cursor,
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    execute, queue,
+    style::{Attribute, Print, SetAttribute},
+    terminal,
+};
+use rust_ls::{
+    DirListing, Entry, EntryFilter, FileKind, LongFormatter, SortKey, SortOptions,
+    color::{ColorWhen, LsColors},
+    content,
+    quoting::Quoting,
+    sort::sort_entries,
+    usage::DirectoryTotals,
+};
+use unicode_width::UnicodeWidthChar;
+
+use crate::{cli::Cli, describe};
+
+/// How much of a text file the preview pane reads.
+const PREVIEW_BYTES: u64 = 64 * 1024;
+/// Narrowest terminal that still has room for the preview pane next to the long format.
+const PREVIEW_MIN_WIDTH: usize = 100;
+const HELP: &str = "↑↓ move  → open  ← parent  n s t v x u sort  r reverse  d dirs first  \
+                    . dotfiles  l long  1-6 columns  p preview  R reload  q quit";
+
+/// Browses `path` full screen until the user quits (`--browse`).
+pub fn run(cli: &Cli, path: &Path) -> io::Result<()> {
+    let mut browser = Browser::new(cli, fs::canonicalize(path)?);
+    browser.reload();
+
+    let _terminal = Terminal::enter()?;
+    let mut out = io::BufWriter::new(io::stdout().lock());
+    loop {
+        browser.draw(&mut out)?;
+        // anything else, e.g. a resize, only calls for drawing again
+        if let Event::Key(key) = event::read()?
+            && key.kind != KeyEventKind::Release
+            && !browser.handle(key)
+        {
+            return Ok(());
+        }
+    }
+}
+
+/// Raw mode on the alternate screen, for as long as it lives.
+struct Terminal;
+
+impl Terminal {
+    fn enter() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        // from here on, the terminal is restored however this goes
+        let terminal = Terminal;
+        execute!(io::stdout(), terminal::EnterAlternateScreen, cursor::Hide)?;
+        Ok(terminal)
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        let _ = execute!(io::stdout(), cursor::Show, terminal::LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// The directory on screen, and how it is shown.
+struct Browser<'a> {
+    cli: &'a Cli,
+    filter: Arc<EntryFilter>,
+    /// the same formatter as `-l`, with the columns the user turned off hidden
+    formatter: LongFormatter,
+    colors: Option<LsColors>,
+    /// recursive sizes below the directory that was last loaded with R (or started in), with
+    /// `--du`
+    totals: Option<Arc<DirectoryTotals>>,
+    dir: PathBuf,
+    entries: Vec<Entry>,
+    /// index of the highlighted entry, and of the first one on screen
+    selected: usize,
+    scroll: usize,
+    /// entries that fit on screen, for Page Up and Page Down
+    page: usize,
+    sort: SortOptions,
+    dotfiles: bool,
+    long: bool,
+    preview: bool,
+    /// the preview of the highlighted entry, and which entry it is of
+    preview_lines: Vec<String>,
+    preview_of: Option<PathBuf>,
+    /// what went wrong with the last key, shown in the status line
+    message: Option<String>,
+}
+
+impl<'a> Browser<'a> {
+    fn new(cli: &'a Cli, dir: PathBuf) -> Self {
+        // the browser always writes to a terminal
+        let colors = cli
+            .color
+            .unwrap_or(ColorWhen::Auto)
+            .enabled()
+            .then(LsColors::from_env);
+        Browser {
+            cli,
+            filter: Arc::new(cli.entry_filter()),
+            formatter: cli.long_formatter(),
+            colors,
+            totals: None,
+            dir,
+            entries: Vec::new(),
+            selected: 0,
+            scroll: 0,
+            page: 1,
+            sort: cli.sort_options(),
+            dotfiles: cli.all || cli.almost_all,
+            long: true,
+            preview: true,
+            preview_lines: Vec::new(),
+            preview_of: None,
+            message: None,
+        }
+    }
+
+    /// Handles one key press. Returns `false` when the user quits.
+    fn handle(&mut self, key: KeyEvent) -> bool {
+        self.message = None;
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            // raw mode turns off the signal, so interrupting is up to us
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return false,
+            KeyCode::Up | KeyCode::Char('k') => self.select(self.selected.saturating_sub(1)),
+            KeyCode::Down | KeyCode::Char('j') => self.select(self.selected + 1),
+            KeyCode::PageUp => self.select(self.selected.saturating_sub(self.page)),
+            KeyCode::PageDown => self.select(self.selected + self.page),
+            KeyCode::Home | KeyCode::Char('g') => self.select(0),
+            KeyCode::End | KeyCode::Char('G') => self.select(usize::MAX),
+            KeyCode::Right | KeyCode::Enter => self.enter(),
+            KeyCode::Left | KeyCode::Backspace => self.leave(),
+            KeyCode::Char('n') => self.sort_by(SortKey::Name),
+            KeyCode::Char('s') => self.sort_by(SortKey::Size),
+            KeyCode::Char('t') => self.sort_by(SortKey::Time),
+            KeyCode::Char('v') => self.sort_by(SortKey::Version),
+            KeyCode::Char('x') => self.sort_by(SortKey::Extension),
+            KeyCode::Char('u') => self.sort_by(SortKey::None),
+            KeyCode::Char('r') => {
+                self.sort.reverse = !self.sort.reverse;
+                self.sort_by(self.sort.key);
+            },
+            KeyCode::Char('d') => {
+                self.sort.directories_first = !self.sort.directories_first;
+                self.sort_by(self.sort.key);
+            },
+            KeyCode::Char('.') => {
+                self.dotfiles = !self.dotfiles;
+                self.open(self.dir.clone(), self.selected_name());
+            },
+            KeyCode::Char('l') => self.long = !self.long,
+            KeyCode::Char(digit @ '1'..='6') => {
+                let columns = &mut self.formatter.columns;
+                let column = match digit {
+                    '1' => &mut columns.mode,
+                    '2' => &mut columns.links,
+                    '3' => &mut columns.owner,
+                    '4' => &mut columns.group,
+                    '5' => &mut columns.size,
+                    _ => &mut columns.time,
+                };
+                *column = !*column;
+            },
+            KeyCode::Char('p') => self.preview = !self.preview,
+            KeyCode::Char('R') => self.reload(),
+            _ => {},
+        }
+        true
+    }
+
+    /// Reads the directory again, and with `--du` the sizes below it.
+    fn reload(&mut self) {
+        if self.cli.du {
+            self.totals = match DirectoryTotals::walk(&self.dir, self.cli.one_file_system) {
+                Ok((_, totals)) => {
+                    if !totals.errors.is_empty() {
+                        self.message =
+                            Some(format!("{} files could not be measured", totals.errors.len()));
+                    }
+                    Some(Arc::new(totals))
+                },
+                Err(_) => None,
+            };
+        }
+        // a file preview may be out of date even if the path is the same
+        self.preview_of = None;
+        self.open(self.dir.clone(), self.selected_name());
+    }
+
+    /// Shows `dir`, highlighting the entry named `select` if it is there. A directory that
+    /// cannot be read leaves the current one on screen.
+    fn open(&mut self, dir: PathBuf, select: Option<OsString>) {
+        let listing = self
+            .listing(&dir)
+            .xattrs(true)
+            .contents(self.cli.content_options())
+            .totals(self.totals.clone())
+            .dereference(self.cli.dereference);
+        match listing.read_with_errors() {
+            Ok((entries, errors)) => {
+                if !errors.is_empty() {
+                    self.message = Some(format!("{} entries could not be read", errors.len()));
+                }
+                self.selected = select
+                    .and_then(|name| entries.iter().position(|entry| entry.name == name))
+                    .unwrap_or(0);
+                self.scroll = 0;
+                self.entries = entries;
+                self.dir = dir;
+            },
+            Err(err) => {
+                self.message =
+                    Some(format!("cannot open directory '{}': {}", dir.display(), describe(&err)));
+            },
+        }
+    }
+
+    /// The entries of `dir` as the browser shows them, before anything is read from them.
+    fn listing(&self, dir: &Path) -> DirListing {
+        DirListing::new(dir)
+            .almost_all(self.dotfiles)
+            .sort(self.sort)
+            .filter(self.filter.clone())
+    }
+
+    /// Opens the highlighted entry if it is a directory, or a link to one.
+    fn enter(&mut self) {
+        let Some(entry) = self.entries.get(self.selected) else {
+            return;
+        };
+        if entry.is_dir() || fs::metadata(&entry.path).is_ok_and(|metadata| metadata.is_dir()) {
+            self.open(entry.path.clone(), None);
+        }
+    }
+
+    /// Goes up to the parent directory, highlighting the one that was left.
+    fn leave(&mut self) {
+        if let Some(parent) = self.dir.parent() {
+            let name = self.dir.file_name().map(OsString::from);
+            self.open(parent.to_path_buf(), name);
+        }
+    }
+
+    fn select(&mut self, index: usize) {
+        self.selected = index.min(self.entries.len().saturating_sub(1));
+    }
+
+    fn selected_name(&self) -> Option<OsString> {
+        self.entries
+            .get(self.selected)
+            .map(|entry| entry.name.clone())
+    }
+
+    /// Sorts by `key` with the current direction, keeping the same entry highlighted.
+    fn sort_by(&mut self, key: SortKey) {
+        let selected = self.selected_name();
+        self.sort.key = key;
+        sort_entries(&mut self.entries, &self.sort);
+        self.selected = selected
+            .and_then(|name| self.entries.iter().position(|entry| entry.name == name))
+            .unwrap_or(0);
+    }
+
+    /// Draws the whole screen: the directory at the top, the entries with the preview next to
+    /// them, and the status and key help at the bottom. Every line is padded to the full width,
+    /// so nothing of the previous screen is left.
+    fn draw(&mut self, out: &mut impl Write) -> io::Result<()> {
+        let (width, height) = terminal::size()?;
+        let (width, height) = (width as usize, height as usize);
+        if height < 3 {
+            return Ok(());
+        }
+        let rows = height - 3;
+        self.page = rows.max(1);
+        // keep the highlighted entry on screen
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if self.selected >= self.scroll + self.page {
+            self.scroll = self.selected + 1 - self.page;
+        }
+
+        let preview_width = if self.preview && width >= PREVIEW_MIN_WIDTH { width / 3 } else { 0 };
+        let list_width = if preview_width > 0 { width - preview_width - 1 } else { width };
+        if preview_width > 0 {
+            self.update_preview();
+        }
+
+        let header = to_text(&self.formatter.quoting, self.dir.as_os_str());
+        queue!(
+            out,
+            cursor::MoveTo(0, 0),
+            SetAttribute(Attribute::Reverse),
+            Print(fit(&header, width)),
+            SetAttribute(Attribute::Reset)
+        )?;
+        for row in 0..rows {
+            queue!(out, cursor::MoveTo(0, row as u16 + 1))?;
+            let index = self.scroll + row;
+            match self.entries.get(index) {
+                Some(entry) => self.draw_entry(out, entry, list_width, index == self.selected)?,
+                None => queue!(out, Print(fit("", list_width)))?,
+            }
+            if preview_width > 0 {
+                let line = self.preview_lines.get(row).map_or("", String::as_str);
+                queue!(out, Print("│"), Print(fit(line, preview_width)))?;
+            }
+        }
+
+        queue!(
+            out,
+            cursor::MoveTo(0, height as u16 - 2),
+            Print(fit(&self.status(), width)),
+            cursor::MoveTo(0, height as u16 - 1),
+            SetAttribute(Attribute::Dim),
+            Print(fit(HELP, width)),
+            SetAttribute(Attribute::Reset)
+        )?;
+        out.flush()
+    }
+
+    /// One row of the listing: the long format columns, if shown, and the name. The highlighted
+    /// row is in reverse video, the others have their names colored like in a listing.
+    fn draw_entry(
+        &self,
+        out: &mut impl Write,
+        entry: &Entry,
+        width: usize,
+        selected: bool,
+    ) -> io::Result<()> {
+        let columns = if self.long {
+            format!("{} ", self.formatter.format_columns(entry))
+        } else {
+            String::new()
+        };
+        let quoting = &self.formatter.quoting;
+        let mut name = to_text(quoting, &entry.name);
+        if let Some(target) = &entry.link_target {
+            name = format!("{} -> {}", name, to_text(quoting, target.as_os_str()));
+        }
+
+        if selected {
+            return queue!(
+                out,
+                SetAttribute(Attribute::Reverse),
+                Print(fit(&format!("{}{}", columns, name), width)),
+                SetAttribute(Attribute::Reset)
+            );
+        }
+        let (columns, columns_width) = truncate(&columns, width);
+        let (name, name_width) = truncate(&name, width - columns_width);
+        let name = match &self.colors {
+            Some(colors) => {
+                String::from_utf8_lossy(&colors.paint(name.as_bytes(), colors.style(entry)))
+                    .into_owned()
+            },
+            None => name.to_string(),
+        };
+        let padding = " ".repeat(width - columns_width - name_width);
+        queue!(out, Print(columns), Print(name), Print(padding))
+    }
+
+    /// Where the highlighted entry is, how the entries are sorted, and any error.
+    fn status(&self) -> String {
+        let position = match self.entries.len() {
+            0 => "empty".to_string(),
+            count => format!("{}/{}", self.selected + 1, count),
+        };
+        let key = self
+            .sort
+            .key
+            .to_possible_value()
+            .map_or_else(String::new, |value| value.get_name().to_string());
+        let mut status = format!("{}  sort: {}", position, key);
+        if self.sort.reverse {
+            status.push_str(", reversed");
+        }
+        if self.sort.directories_first {
+            status.push_str(", directories first");
+        }
+        if self.dotfiles {
+            status.push_str("  dotfiles shown");
+        }
+        if let Some(message) = &self.message {
+            status.push_str("  ");
+            status.push_str(message);
+        }
+        status
+    }
+
+    /// Reads the preview again if another entry was highlighted since it was last read.
+    fn update_preview(&mut self) {
+        let entry = self.entries.get(self.selected);
+        if self.preview_of.as_ref() == entry.map(|entry| &entry.path) {
+            return;
+        }
+        self.preview_lines = entry
+            .map(|entry| self.read_preview(entry))
+            .unwrap_or_default();
+        self.preview_of = entry.map(|entry| entry.path.clone());
+    }
+
+    /// What the preview pane shows for `entry`: the names in a directory, the first lines of a
+    /// text file, and the type and size of anything else. Symbolic links are previewed by what
+    /// they point to.
+    fn read_preview(&self, entry: &Entry) -> Vec<String> {
+        let quoting = &self.formatter.quoting;
+        let mut lines = Vec::new();
+        if let Some(target) = &entry.link_target {
+            lines.push(format!("-> {}", to_text(quoting, target.as_os_str())));
+        }
+        let metadata = match fs::metadata(&entry.path) {
+            Ok(metadata) => metadata,
+            Err(err) => {
+                lines.push(describe(&err));
+                return lines;
+            },
+        };
+
+        let kind = FileKind::from_file_type(&metadata.file_type());
+        match kind {
+            FileKind::Directory => match self.listing(&entry.path).read() {
+                Ok(entries) if entries.is_empty() => lines.push("(empty directory)".to_string()),
+                Ok(entries) => lines.extend(entries.iter().map(|entry| {
+                    let name = to_text(quoting, &entry.name);
+                    if entry.is_dir() { format!("{}/", name) } else { name }
+                })),
+                Err(err) => lines.push(describe(&err)),
+            },
+            _ => match content::mime_type(&entry.path, kind, metadata.len()) {
+                Some(mime_type) if mime_type.starts_with("text/") => match read_text(&entry.path) {
+                    Ok(text) => lines.extend(text),
+                    Err(err) => lines.push(describe(&err)),
+                },
+                Some(mime_type) if kind == FileKind::File => {
+                    lines.push(format!("{}, {} bytes", mime_type, metadata.len()))
+                },
+                Some(mime_type) => lines.push(mime_type.to_string()),
+                None => lines.push("cannot be read".to_string()),
+            },
+        }
+        lines
+    }
+}
+
+/// The lines at the start of a text file, with tabs expanded and other control characters
+/// replaced, so that they cannot move the cursor.
+fn read_text(path: &Path) -> io::Result<Vec<String>> {
+    let mut head = Vec::new();
+    File::open(path)?
+        .take(PREVIEW_BYTES)
+        .read_to_end(&mut head)?;
+    let lines = String::from_utf8_lossy(&head)
+        .lines()
+        .map(|line| line.replace('\t', "    ").replace(char::is_control, "?"))
+        .collect();
+    Ok(lines)
+}
+
+/// `name` quoted for the screen, which is drawn from text: bytes that are not UTF-8 show as
+/// U+FFFD unless the quoting style escapes them.
+fn to_text(quoting: &Quoting, name: &OsStr) -> String {
+    String::from_utf8_lossy(&quoting.quote(name)).into_owned()
+}
+
+/// The start of `text` that fits in `width` terminal columns, and how many columns it takes.
+fn truncate(text: &str, width: usize) -> (&str, usize) {
+    let mut used = 0;
+    for (index, c) in text.char_indices() {
+        let char_width = c.width().unwrap_or(0);
+        if used + char_width > width {
+            return (&text[..index], used);
+        }
+        used += char_width;
+    }
+    (text, used)
+}
+
+/// `text` cut or padded with spaces to exactly `width` terminal columns.
+fn fit(text: &str, width: usize) -> String {
+    let (text, used) = truncate(text, width);
+    format!("{}{}", text, " ".repeat(width - used))
+}
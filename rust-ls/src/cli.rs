@@ -0,0 +1,505 @@
+use std::{io::IsTerminal, num::NonZeroUsize, path::PathBuf, sync::Arc};
+
+use clap::{ArgAction, Parser, ValueEnum};
+
+use rust_ls::{
+    BlockSize, Checksum, ContentOptions, EntryFilter, LongFormatter, Quoting, QuotingStyle,
+    TimeField, TimeStyle,
+    block_size::parse_size,
+    color::ColorWhen,
+    filter::OnlyKind,
+    format::Columns,
+    sort::{SortKey, SortOptions},
+};
+
+/// List information about the FILEs (the current directory by default).
+///
+/// Flags follow GNU `ls`: short flags can be bundled (`-la`), and when two flags select
+/// conflicting modes (e.g. `-l` and `-1`), the one given last wins.
+#[derive(Debug, Parser)]
+#[command(name = "rust-ls", version, disable_help_flag = true, args_override_self = true)]
+pub struct Cli {
+    /// with -l, list the extended attributes of each entry and their sizes
+    #[arg(short = '@', long = "extended")]
+    pub xattrs: bool,
+
+    /// do not ignore entries whose name starts with a dot
+    #[arg(short = 'a', long, overrides_with = "almost_all")]
+    pub all: bool,
+
+    /// do not list implied . and ..
+    #[arg(short = 'A', long, overrides_with = "all")]
+    pub almost_all: bool,
+
+    /// list entries by columns
+    #[arg(short = 'C', overrides_with_all = ["format", "long", "across", "one_per_line", "tree"])]
+    pub columns: bool,
+
+    /// list tar, tar.gz and zip FILEs as directories of what they hold; a path inside an archive
+    /// (release.zip/bin) or an archive followed by a slash (release.zip/) is always listed so
+    #[arg(long)]
+    pub archive: bool,
+
+    /// scale sizes by SIZE before printing them; e.g. '--block-size=M' prints sizes in units of
+    /// 1,048,576 bytes, and '--block-size=1' prints exact byte counts
+    #[arg(long, value_name = "SIZE", overrides_with_all = ["human_readable", "si"])]
+    pub block_size: Option<BlockSize>,
+
+    /// browse the directory FILE full screen: move with the arrow keys, preview files, and sort
+    /// or toggle long format columns with hotkeys (press q to quit)
+    #[arg(long)]
+    pub browse: bool,
+
+    /// print a hash of the contents of each regular file: sha256 or blake3
+    #[arg(long, value_name = "ALGORITHM")]
+    pub checksum: Option<Checksum>,
+
+    /// colorize the output; WHEN can be 'always' (default if omitted), 'auto', or 'never'
+    #[arg(
+        long,
+        value_name = "WHEN",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "always"
+    )]
+    pub color: Option<ColorWhen>,
+
+    /// print C-style escapes for nongraphic characters
+    #[arg(short = 'b', long, overrides_with_all = ["quoting_style", "literal", "quote_name"])]
+    pub escape: bool,
+
+    /// with -lt: sort by, and show, ctime (time of last change of file status information)
+    #[arg(short = 'c', overrides_with_all = ["time", "access_time"])]
+    pub change_time: bool,
+
+    /// print any security context (SELinux label) of each entry
+    #[arg(short = 'Z', long)]
+    pub context: bool,
+
+    /// compare the two FILEs, each a snapshot saved with --snapshot or a directory, and print the
+    /// files added (+), removed (-) and changed (~) with what changed about them; exits with 1
+    /// if anything differs
+    #[arg(long)]
+    pub diff: bool,
+
+    /// list directories themselves, not their contents
+    #[arg(short = 'd', long)]
+    pub directory: bool,
+
+    /// show directories with the recursive apparent and allocated size of what they hold, and
+    /// print the size of everything listed at the end, counting files with several hard links
+    /// once; -S then sorts by these totals
+    #[arg(long, visible_alias = "summarize")]
+    pub du: bool,
+
+    /// across -x, long -l, single-column -1, vertical -C, tree, json, ndjson
+    #[arg(long, value_name = "WORD", overrides_with_all = ["columns", "long", "across", "one_per_line", "tree"])]
+    pub format: Option<Format>,
+
+    /// like -l --time-style=full-iso
+    #[arg(long)]
+    pub full_time: bool,
+
+    /// with -l, show the git status of each entry: staged and unstaged changes, rolled up for
+    /// directories (N new, M modified, D deleted, R renamed, T type change, I ignored,
+    /// U conflicted)
+    #[arg(long)]
+    pub git: bool,
+
+    /// do not list entries that git ignores, following .gitignore files as the listing recurses
+    #[arg(long)]
+    pub git_ignore: bool,
+
+    /// group directories before files
+    #[arg(long)]
+    pub group_directories_first: bool,
+
+    /// mark files that are hard links to the same inode with a shared group number, like #1
+    #[arg(long)]
+    pub group_hardlinks: bool,
+
+    /// print ? instead of nongraphic characters (the default on a terminal)
+    #[arg(short = 'q', long, overrides_with = "show_control_chars")]
+    pub hide_control_chars: bool,
+
+    /// follow symbolic links listed on the command line
+    #[arg(short = 'H', long)]
+    pub dereference_command_line: bool,
+
+    /// do not list implied entries matching shell PATTERN (overridden by -a or -A)
+    #[arg(long, value_name = "PATTERN")]
+    pub hide: Vec<String>,
+
+    /// print the index number of each file
+    #[arg(short = 'i', long)]
+    pub inode: bool,
+
+    /// do not list implied entries matching shell PATTERN
+    #[arg(short = 'I', long, value_name = "PATTERN")]
+    pub ignore: Vec<String>,
+
+    /// with -l and -s, print sizes like 1K 234M 2G etc.
+    #[arg(short = 'h', long, overrides_with_all = ["si", "block_size"])]
+    pub human_readable: bool,
+
+    /// with --tree, descend at most N levels below each FILE
+    #[arg(long, value_name = "N")]
+    pub level: Option<NonZeroUsize>,
+
+    /// list subdirectories recursively, at most N levels below each FILE (0 lists FILE only)
+    #[arg(long, value_name = "N")]
+    pub max_depth: Option<usize>,
+
+    /// do not list files larger than SIZE (e.g. 10K, 2M); directories are always listed
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    pub max_size: Option<u64>,
+
+    /// print the MIME type of each entry, guessed from the magic bytes at the start of files
+    #[arg(long)]
+    pub mime: bool,
+
+    /// do not list files smaller than SIZE (e.g. 10K, 2M); directories are always listed
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    pub min_size: Option<u64>,
+
+    /// print entry names without quoting
+    #[arg(short = 'N', long, overrides_with_all = ["quoting_style", "escape", "quote_name"])]
+    pub literal: bool,
+
+    /// use a long listing format
+    #[arg(short = 'l', overrides_with_all = ["format", "columns", "across", "one_per_line", "tree"])]
+    pub long: bool,
+
+    /// show information for the file a symbolic link references rather than for the link
+    /// itself; with -R or --tree, descend into linked directories
+    #[arg(short = 'L', long)]
+    pub dereference: bool,
+
+    /// do not descend into directories on other file systems, when listing recursively or adding
+    /// up sizes with --du
+    #[arg(long)]
+    pub one_file_system: bool,
+
+    /// list only directories
+    #[arg(long, overrides_with = "only_files")]
+    pub only_dirs: bool,
+
+    /// list only files that are not directories
+    #[arg(long, overrides_with = "only_dirs")]
+    pub only_files: bool,
+
+    /// enclose entry names in double quotes
+    #[arg(short = 'Q', long, overrides_with_all = ["quoting_style", "escape", "literal"])]
+    pub quote_name: bool,
+
+    /// use quoting style WORD for entry names
+    ///
+    /// Defaults to $QUOTING_STYLE, then shell-escape on a terminal and literal otherwise.
+    #[arg(long, value_name = "WORD", overrides_with_all = ["escape", "literal", "quote_name"])]
+    pub quoting_style: Option<QuotingStyle>,
+
+    /// reverse order while sorting
+    #[arg(short = 'r', long)]
+    pub reverse: bool,
+
+    /// list subdirectories recursively
+    #[arg(short = 'R', long)]
+    pub recursive: bool,
+
+    /// print the allocated size of each file, in blocks
+    #[arg(short = 's', long)]
+    pub size: bool,
+
+    /// likewise, but use powers of 1000 not 1024
+    #[arg(long, overrides_with_all = ["human_readable", "block_size"])]
+    pub si: bool,
+
+    /// show nongraphic characters as is (the default unless on a terminal)
+    #[arg(long, overrides_with = "hide_control_chars")]
+    pub show_control_chars: bool,
+
+    /// save a recursive listing of the directory FILE to PATH as JSON ('-' for standard output),
+    /// with hashes of regular files if --checksum is given, to compare later with --diff
+    #[arg(long, value_name = "PATH")]
+    pub snapshot: Option<PathBuf>,
+
+    /// sort by WORD instead of name
+    #[arg(long, value_name = "WORD", overrides_with_all = ["by_size", "by_time", "unsorted", "by_version", "by_extension"])]
+    pub sort: Option<SortKey>,
+
+    /// select which timestamp to show and sort by: atime, ctime, mtime or birth
+    #[arg(long, value_name = "WORD", overrides_with_all = ["access_time", "change_time"])]
+    pub time: Option<TimeField>,
+
+    /// time/date format with -l: full-iso, long-iso, iso, locale or +FORMAT
+    ///
+    /// FORMAT is interpreted like in strftime. With two formats separated by a newline
+    /// (+FORMAT1<newline>FORMAT2), FORMAT1 applies to recent files and FORMAT2 to files older
+    /// than six months. Defaults to $TIME_STYLE, then locale.
+    #[arg(long, value_name = "STYLE")]
+    pub time_style: Option<TimeStyle>,
+
+    /// sort by file size, largest first
+    #[arg(short = 'S', overrides_with_all = ["sort", "by_time", "unsorted", "by_version", "by_extension"])]
+    pub by_size: bool,
+
+    /// sort by time, newest first
+    #[arg(short = 't', overrides_with_all = ["sort", "by_size", "unsorted", "by_version", "by_extension"])]
+    pub by_time: bool,
+
+    /// list subdirectories recursively as a tree; with -d, list only directories
+    #[arg(long, overrides_with_all = ["format", "columns", "long", "across", "one_per_line"])]
+    pub tree: bool,
+
+    /// with -lt: sort by, and show, access time
+    #[arg(short = 'u', overrides_with_all = ["time", "change_time"])]
+    pub access_time: bool,
+
+    /// do not sort; list entries in directory order
+    #[arg(short = 'U', overrides_with_all = ["sort", "by_size", "by_time", "by_version", "by_extension"])]
+    pub unsorted: bool,
+
+    /// natural sort of (version) numbers within text
+    #[arg(short = 'v', overrides_with_all = ["sort", "by_size", "by_time", "unsorted", "by_extension"])]
+    pub by_version: bool,
+
+    /// sort alphabetically by entry extension
+    #[arg(short = 'X', overrides_with_all = ["sort", "by_size", "by_time", "unsorted", "by_version"])]
+    pub by_extension: bool,
+
+    /// list a directory again whenever its entries are created, changed or removed, marking new
+    /// (+) and changed (~) rows
+    #[arg(long)]
+    pub watch: bool,
+
+    /// set output width to COLS; 0 means no limit
+    #[arg(short = 'w', long, value_name = "COLS")]
+    pub width: Option<usize>,
+
+    /// list entries by lines instead of by columns
+    #[arg(short = 'x', overrides_with_all = ["format", "columns", "long", "one_per_line", "tree"])]
+    pub across: bool,
+
+    /// list one file per line
+    #[arg(short = '1', overrides_with_all = ["format", "columns", "long", "across", "tree"])]
+    pub one_per_line: bool,
+
+    /// display this help and exit
+    #[arg(long, action = ArgAction::Help)]
+    help: Option<bool>,
+
+    /// files to list
+    #[arg(value_name = "FILE")]
+    pub paths: Vec<PathBuf>,
+}
+
+/// How entries are laid out.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// names in rows, filled left to right (-x)
+    #[value(alias = "horizontal")]
+    Across,
+    /// a JSON array with one record per entry
+    Json,
+    /// permissions, owner, size and time on one line per entry (-l)
+    #[value(alias = "verbose")]
+    Long,
+    /// one JSON record per line
+    Ndjson,
+    /// names below their directories, joined by box-drawing branches (--tree)
+    Tree,
+    /// one name per line (-1)
+    SingleColumn,
+    /// names in columns, filled top to bottom (-C)
+    Vertical,
+}
+
+impl Cli {
+    /// The layout from whichever of `--format`, `-C`, `-l`, `-x`, `-1` and `--tree` was given
+    /// last.
+    ///
+    /// Without any of them, names are put in columns on a terminal and one per line otherwise,
+    /// so that the output stays easy to process in a pipe.
+    pub fn format(&self) -> Format {
+        if let Some(format) = self.format {
+            format
+        } else if self.long || self.full_time {
+            Format::Long
+        } else if self.columns {
+            Format::Vertical
+        } else if self.across {
+            Format::Across
+        } else if self.tree {
+            Format::Tree
+        } else if self.one_per_line || !std::io::stdout().is_terminal() {
+            Format::SingleColumn
+        } else {
+            Format::Vertical
+        }
+    }
+
+    /// Whether symbolic links given as operands are followed (`-H`, or `-L` for every link).
+    pub fn follows_operand_links(&self) -> bool {
+        self.dereference || self.dereference_command_line
+    }
+
+    /// Whether entries need their extended attributes, for the `+`/`@` markers of the long format,
+    /// `-@` or `-Z`.
+    pub fn needs_xattrs(&self) -> bool {
+        self.format() == Format::Long || self.xattrs || self.context
+    }
+
+    /// The columns read from file contents (`--checksum`, `--mime`).
+    pub fn content_options(&self) -> ContentOptions {
+        ContentOptions { mime_type: self.mime, checksum: self.checksum }
+    }
+
+    /// The `-l` line as the options ask for it, with every column shown.
+    pub fn long_formatter(&self) -> LongFormatter {
+        LongFormatter {
+            size: Box::new(self.size_unit()),
+            time: Box::new(self.time_style()),
+            time_field: self.time_field(),
+            quoting: self.quoting(),
+            git: self.git.then(Arc::default),
+            context: self.context,
+            contents: self.content_options(),
+            columns: Columns::default(),
+        }
+    }
+
+    /// Whether subdirectories are listed too (`-R`, or `--max-depth` for a limited depth).
+    pub fn is_recursive(&self) -> bool {
+        self.recursive || self.max_depth.is_some()
+    }
+
+    /// Whether the output is machine-readable records rather than text.
+    pub fn is_json(&self) -> bool {
+        matches!(self.format(), Format::Json | Format::Ndjson)
+    }
+
+    /// The sort key from whichever of `--sort`, `-S`, `-t`, `-U`, `-v` and `-X` was given last.
+    pub fn sort_options(&self) -> SortOptions {
+        let key = if let Some(key) = self.sort {
+            key
+        } else if self.by_size {
+            SortKey::Size
+        } else if self.by_time {
+            SortKey::Time
+        } else if self.unsorted {
+            SortKey::None
+        } else if self.by_version {
+            SortKey::Version
+        } else if self.by_extension {
+            SortKey::Extension
+        } else {
+            SortKey::Name
+        };
+        SortOptions {
+            key,
+            reverse: self.reverse,
+            directories_first: self.group_directories_first,
+            time_field: self.time_field(),
+        }
+    }
+
+    /// The timestamp from whichever of `--time`, `-u` and `-c` was given last.
+    pub fn time_field(&self) -> TimeField {
+        if let Some(field) = self.time {
+            field
+        } else if self.access_time {
+            TimeField::Access
+        } else if self.change_time {
+            TimeField::Change
+        } else {
+            TimeField::Modification
+        }
+    }
+
+    /// The unit from whichever of `-h`, `--si` and `--block-size` was given last, then
+    /// `$LS_BLOCK_SIZE` and `$BLOCK_SIZE`.
+    fn block_size(&self) -> Option<BlockSize> {
+        if self.human_readable {
+            Some(BlockSize::Human { base: 1024 })
+        } else if self.si {
+            Some(BlockSize::Human { base: 1000 })
+        } else if let Some(block_size) = &self.block_size {
+            Some(block_size.clone())
+        } else {
+            BlockSize::from_env()
+        }
+    }
+
+    /// Unit of the size column, exact bytes by default.
+    pub fn size_unit(&self) -> BlockSize {
+        self.block_size().unwrap_or_else(BlockSize::bytes)
+    }
+
+    /// Unit of the `total` line and the `-s` column, 1024-byte blocks by default.
+    pub fn blocks_unit(&self) -> BlockSize {
+        self.block_size().unwrap_or_else(BlockSize::kibibytes)
+    }
+
+    /// Unit of the `--du` summary, which is read on its own and so labels its sizes like `-h`
+    /// by default.
+    pub fn usage_unit(&self) -> BlockSize {
+        self.block_size().unwrap_or(BlockSize::Human { base: 1024 })
+    }
+
+    /// What `-I`, `--hide`, `--git-ignore`, `--only-dirs`, `--only-files` and the size limits
+    /// leave out of directory listings.
+    pub fn entry_filter(&self) -> EntryFilter {
+        let only = if self.only_dirs {
+            Some(OnlyKind::Directories)
+        } else if self.only_files {
+            Some(OnlyKind::Files)
+        } else {
+            None
+        };
+        EntryFilter::default()
+            .ignore(&self.ignore)
+            .hide(&self.hide)
+            .git_ignore(self.git_ignore)
+            .only(only)
+            .size_range(self.min_size, self.max_size)
+    }
+
+    /// The style from whichever of `--quoting-style`, `-b`, `-N` and `-Q` was given last.
+    pub fn quoting(&self) -> Quoting {
+        let on_terminal = std::io::stdout().is_terminal();
+        let style = if let Some(style) = self.quoting_style {
+            style
+        } else if self.escape {
+            QuotingStyle::Escape
+        } else if self.literal {
+            QuotingStyle::Literal
+        } else if self.quote_name {
+            QuotingStyle::C
+        } else if let Some(style) = QuotingStyle::from_env() {
+            style
+        } else if on_terminal {
+            QuotingStyle::ShellEscape
+        } else {
+            QuotingStyle::Literal
+        };
+        let hide_control_chars = if self.hide_control_chars {
+            true
+        } else if self.show_control_chars {
+            false
+        } else {
+            on_terminal
+        };
+        Quoting { style, hide_control_chars }
+    }
+
+    /// `--full-time` wins over `--time-style`, which wins over `$TIME_STYLE`.
+    pub fn time_style(&self) -> TimeStyle {
+        if self.full_time {
+            TimeStyle::FullIso
+        } else if let Some(style) = &self.time_style {
+            style.clone()
+        } else {
+            TimeStyle::from_env().unwrap_or_default()
+        }
+    }
+}
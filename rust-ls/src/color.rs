@@ -0,0 +1,244 @@
+use std::{collections::HashMap, env, io::IsTerminal};
+
+use crate::entry::{Entry, FileKind};
+
+/// What GNU `dircolors` prints with no configuration, used when `LS_COLORS` is not set.
+const DEFAULT_LS_COLORS: &str = "rs=0:di=01;34:ln=01;36:mh=00:pi=40;33:so=01;35:do=01;35:\
+                                 bd=40;33;01:cd=40;33;01:or=40;31;01:mi=00:su=37;41:sg=30;43:\
+                                 ca=00:tw=30;42:ow=34;42:st=37;44:ex=01;32";
+
+/// When to colorize the output (`--color=WHEN`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum ColorWhen {
+    #[cfg_attr(feature = "cli", value(alias = "yes", alias = "force"))]
+    Always,
+    #[cfg_attr(feature = "cli", value(alias = "tty", alias = "if-tty"))]
+    Auto,
+    #[cfg_attr(feature = "cli", value(alias = "no", alias = "none"))]
+    Never,
+}
+
+impl ColorWhen {
+    pub fn enabled(self) -> bool {
+        match self {
+            ColorWhen::Always => true,
+            ColorWhen::Auto => std::io::stdout().is_terminal(),
+            ColorWhen::Never => false,
+        }
+    }
+}
+
+/// Color database parsed from `LS_COLORS`.
+///
+/// Two-letter keys such as `di` or `ex` are stored as indicators, and `*SUFFIX` keys as
+/// extension patterns. Values are SGR parameters (`01;34`) put between the `lc` and `rc` codes.
+#[derive(Debug, Default)]
+pub struct LsColors {
+    indicators: HashMap<String, String>,
+    /// `(suffix, style)` in the order they were given; the last match wins, like GNU `ls`
+    extensions: Vec<(Vec<u8>, String)>,
+}
+
+impl LsColors {
+    /// Reads `LS_COLORS` on top of the `dircolors` defaults.
+    pub fn from_env() -> Self {
+        LsColors::parse(&env::var("LS_COLORS").unwrap_or_default())
+    }
+
+    /// Parses a `key=value:key=value` specification. Malformed items are skipped, and
+    /// indicators that are not mentioned keep their default, like in GNU `ls`.
+    pub fn parse(spec: &str) -> Self {
+        let mut colors = LsColors::default();
+        colors.apply(DEFAULT_LS_COLORS);
+        colors.apply(spec);
+        colors
+    }
+
+    fn apply(&mut self, spec: &str) {
+        for item in spec.split(':') {
+            let Some((key, value)) = item.split_once('=') else {
+                continue;
+            };
+            let value = unescape(value);
+            if let Some(suffix) = key.strip_prefix('*') {
+                self.extensions
+                    .push((suffix.to_ascii_lowercase().into_bytes(), value));
+            } else if key.len() == 2 {
+                self.indicators.insert(key.to_string(), value);
+            }
+        }
+    }
+
+    /// An indicator's value, or `None` when it is unset or explicitly turned off with `0`/`00`.
+    fn indicator(&self, key: &str) -> Option<&str> {
+        self.indicators
+            .get(key)
+            .map(String::as_str)
+            .filter(|value| !matches!(*value, "" | "0" | "00"))
+    }
+
+    /// Picks the style for an entry like GNU `ls` does: special bits and file types first, then
+    /// the extension for plain files.
+    ///
+    /// Symbolic links are told apart as orphans by [`Entry::link_state`], and with `ln=target`
+    /// colored like the file they lead to.
+    pub fn style(&self, entry: &Entry) -> Option<&str> {
+        let mode = entry.mode;
+        match entry.kind {
+            FileKind::Symlink if entry.is_dangling() => {
+                self.indicator("or").or_else(|| self.indicator("ln"))
+            },
+            FileKind::Symlink => match linked_entry(entry) {
+                Some(target) if self.indicators.get("ln").is_some_and(|ln| ln == "target") => {
+                    self.style(&target)
+                },
+                _ => self.indicator("ln"),
+            },
+            FileKind::Directory => {
+                let sticky = mode & libc::S_ISVTX != 0;
+                let other_writable = mode & libc::S_IWOTH != 0;
+                let key = match (sticky, other_writable) {
+                    (true, true) => "tw",
+                    (false, true) => "ow",
+                    (true, false) => "st",
+                    (false, false) => "di",
+                };
+                self.indicator(key).or_else(|| self.indicator("di"))
+            },
+            FileKind::Fifo => self.indicator("pi"),
+            FileKind::Socket => self.indicator("so"),
+            FileKind::BlockDevice => self.indicator("bd"),
+            FileKind::CharDevice => self.indicator("cd"),
+            FileKind::File => {
+                let special = if mode & libc::S_ISUID != 0 {
+                    self.indicator("su")
+                } else if mode & libc::S_ISGID != 0 {
+                    self.indicator("sg")
+                } else if mode & 0o111 != 0 {
+                    self.indicator("ex")
+                } else if entry.nlink > 1 {
+                    self.indicator("mh")
+                } else {
+                    None
+                };
+                special
+                    .or_else(|| self.extension_style(entry))
+                    .or_else(|| self.indicator("fi"))
+            },
+        }
+    }
+
+    /// Style for the target of a symbolic link in the long format: that of the file it leads
+    /// to, or of a missing file if it is broken.
+    pub fn target_style(&self, entry: &Entry) -> Option<&str> {
+        match linked_entry(entry) {
+            Some(target) => self.style(&target),
+            None if entry.is_dangling() => self.missing_style(),
+            None => None,
+        }
+    }
+
+    /// Style for the target of a broken symbolic link in the long format.
+    pub fn missing_style(&self) -> Option<&str> {
+        self.indicator("mi").or_else(|| self.indicator("or"))
+    }
+
+    fn extension_style(&self, entry: &Entry) -> Option<&str> {
+        let name = entry.name.as_encoded_bytes().to_ascii_lowercase();
+        self.extensions
+            .iter()
+            .rev()
+            .find(|(suffix, _)| name.ends_with(suffix))
+            .map(|(_, style)| style.as_str())
+    }
+
+    /// Wraps `text` in the escape sequences for `style`. Unstyled text is returned as is.
+    pub fn paint(&self, text: &[u8], style: Option<&str>) -> Vec<u8> {
+        let Some(style) = style else {
+            return text.to_vec();
+        };
+        let left = self.indicators.get("lc").map_or("\x1b[", String::as_str);
+        let right = self.indicators.get("rc").map_or("m", String::as_str);
+        let end = match self.indicators.get("ec") {
+            Some(end) => end.clone(),
+            None => {
+                let reset = self.indicators.get("rs").map_or("0", String::as_str);
+                format!("{}{}{}", left, reset, right)
+            },
+        };
+        let mut result = format!("{}{}{}", left, style, right).into_bytes();
+        result.extend_from_slice(text);
+        result.extend_from_slice(end.as_bytes());
+        result
+    }
+}
+
+/// The file a symbolic link leads to, named after the link's target, if it resolves.
+fn linked_entry(entry: &Entry) -> Option<Entry> {
+    let linked = entry.linked_file?;
+    let mut target = entry.clone();
+    if let Some(name) = entry
+        .link_target
+        .as_ref()
+        .and_then(|target| target.file_name())
+    {
+        target.name = name.to_os_string();
+    }
+    target.kind = linked.kind;
+    target.mode = linked.mode;
+    target.nlink = linked.nlink;
+    target.link_target = None;
+    target.link_state = None;
+    target.linked_file = None;
+    Some(target)
+}
+
+/// Expands the escapes `dircolors` accepts in values: `\e`, `\n`-style letters, octal `\NNN`,
+/// and caret notation such as `^[`.
+fn unescape(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('e') => result.push('\x1b'),
+                Some('a') => result.push('\x07'),
+                Some('b') => result.push('\x08'),
+                Some('f') => result.push('\x0c'),
+                Some('n') => result.push('\n'),
+                Some('r') => result.push('\r'),
+                Some('t') => result.push('\t'),
+                Some('v') => result.push('\x0b'),
+                Some('_') => result.push(' '),
+                Some(digit @ '0'..='7') => {
+                    let mut code = digit.to_digit(8).unwrap_or(0);
+                    for _ in 0..2 {
+                        match chars.peek().and_then(|c| c.to_digit(8)) {
+                            Some(next) => {
+                                code = code * 8 + next;
+                                chars.next();
+                            },
+                            None => break,
+                        }
+                    }
+                    result.extend(char::from_u32(code));
+                },
+                Some(other) => result.push(other),
+                None => {},
+            },
+            '^' => match chars.next() {
+                Some('?') => result.push('\x7f'),
+                Some(c @ '@'..='_') => result.push((c as u8 - b'@') as char),
+                Some(c @ 'a'..='z') => result.push((c as u8 - b'a' + 1) as char),
+                Some(other) => {
+                    result.push('^');
+                    result.push(other);
+                },
+                None => result.push('^'),
+            },
+            _ => result.push(c),
+        }
+    }
+    result
+}
@@ -0,0 +1,201 @@
+use std::{
+    fmt,
+    fs::File,
+    io::{self, Read},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
+
+use crate::entry::FileKind;
+
+/// How much of a file is read to guess its type; enough for the tar header magic at 257.
+const MAGIC_LEN: usize = 512;
+
+/// Hash functions for `--checksum`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+#[serde(rename_all = "lowercase")]
+pub enum Checksum {
+    Sha256,
+    Blake3,
+}
+
+impl Checksum {
+    pub fn name(self) -> &'static str {
+        match self {
+            Checksum::Sha256 => "sha256",
+            Checksum::Blake3 => "blake3",
+        }
+    }
+
+    /// Hashes the whole file at `path`.
+    pub fn digest(self, path: &Path) -> io::Result<Digest> {
+        let mut file = File::open(path)?;
+        let bytes = match self {
+            Checksum::Sha256 => {
+                let mut hasher = Sha256::new();
+                io::copy(&mut file, &mut hasher)?;
+                hasher.finalize().to_vec()
+            },
+            Checksum::Blake3 => {
+                let mut hasher = blake3::Hasher::new();
+                io::copy(&mut file, &mut hasher)?;
+                hasher.finalize().as_bytes().to_vec()
+            },
+        };
+        let hex = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+        Ok(Digest { algorithm: self, hex })
+    }
+}
+
+/// The hash of a file's contents.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Digest {
+    pub algorithm: Checksum,
+    /// lowercase hexadecimal, like `sha256sum` and `b3sum` print it
+    pub hex: String,
+}
+
+/// `algorithm:hex`, e.g. `sha256:e3b0c442...`.
+impl fmt::Display for Digest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.algorithm.name(), self.hex)
+    }
+}
+
+/// Which columns computed from file contents are wanted (`--mime`, `--checksum`). Both read
+/// every listed file, so they are off by default.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ContentOptions {
+    pub mime_type: bool,
+    pub checksum: Option<Checksum>,
+}
+
+impl ContentOptions {
+    pub fn is_empty(&self) -> bool {
+        !self.mime_type && self.checksum.is_none()
+    }
+}
+
+/// The MIME type of the file at `path`, like `file --mime-type` reports it: special files and
+/// empty files by their kind (`inode/directory`, `inode/x-empty`), and regular files from the
+/// magic bytes at their start, then as text or binary. `None` if the file cannot be read.
+pub fn mime_type(path: &Path, kind: FileKind, size: u64) -> Option<&'static str> {
+    let mime_type = match kind {
+        FileKind::Directory => "inode/directory",
+        FileKind::Symlink => "inode/symlink",
+        FileKind::BlockDevice => "inode/blockdevice",
+        FileKind::CharDevice => "inode/chardevice",
+        FileKind::Fifo => "inode/fifo",
+        FileKind::Socket => "inode/socket",
+        FileKind::File if size == 0 => "inode/x-empty",
+        FileKind::File => {
+            let mut head = Vec::with_capacity(MAGIC_LEN);
+            File::open(path)
+                .ok()?
+                .take(MAGIC_LEN as u64)
+                .read_to_end(&mut head)
+                .ok()?;
+            sniff(&head)
+        },
+    };
+    Some(mime_type)
+}
+
+/// Guesses a type from the first bytes of a regular file.
+fn sniff(head: &[u8]) -> &'static str {
+    const SIGNATURES: &[(usize, &[u8], &str)] = &[
+        (0, b"\x89PNG\r\n\x1a\n", "image/png"),
+        (0, b"\xff\xd8\xff", "image/jpeg"),
+        (0, b"GIF87a", "image/gif"),
+        (0, b"GIF89a", "image/gif"),
+        (0, b"%PDF-", "application/pdf"),
+        (0, b"PK\x03\x04", "application/zip"),
+        (0, b"PK\x05\x06", "application/zip"),
+        (0, b"\x1f\x8b", "application/gzip"),
+        (0, b"BZh", "application/x-bzip2"),
+        (0, b"\xfd7zXZ\0", "application/x-xz"),
+        (0, b"\x28\xb5\x2f\xfd", "application/zstd"),
+        (0, b"7z\xbc\xaf\x27\x1c", "application/x-7z-compressed"),
+        (257, b"ustar", "application/x-tar"),
+        (0, b"\0asm", "application/wasm"),
+        (0, b"MZ", "application/vnd.microsoft.portable-executable"),
+        (0, b"\xcf\xfa\xed\xfe", "application/x-mach-binary"),
+        (0, b"\xce\xfa\xed\xfe", "application/x-mach-binary"),
+        (0, b"SQLite format 3\0", "application/vnd.sqlite3"),
+        (0, b"ID3", "audio/mpeg"),
+        (0, b"OggS", "audio/ogg"),
+        (0, b"fLaC", "audio/flac"),
+        (4, b"ftyp", "video/mp4"),
+        (0, b"\x1a\x45\xdf\xa3", "video/webm"),
+        (0, b"<?xml", "text/xml"),
+    ];
+    let at = |offset: usize, magic: &[u8]| head.get(offset..offset + magic.len()) == Some(magic);
+    if let Some((_, _, mime_type)) = SIGNATURES
+        .iter()
+        .find(|(offset, magic, _)| at(*offset, magic))
+    {
+        return mime_type;
+    }
+    // RIFF is a container, named after its contents at 8
+    if at(0, b"RIFF") {
+        if at(8, b"WEBP") {
+            return "image/webp";
+        }
+        if at(8, b"WAVE") {
+            return "audio/x-wav";
+        }
+    }
+    if at(0, b"\x7fELF") {
+        // e_type, little or big endian as e_ident says
+        let e_type = match (head.get(5), head.get(16..18)) {
+            (Some(2), Some(&[high, low])) => u16::from_be_bytes([high, low]),
+            (_, Some(&[low, high])) => u16::from_le_bytes([low, high]),
+            _ => 0,
+        };
+        return match e_type {
+            1 => "application/x-object",
+            3 => "application/x-sharedlib",
+            4 => "application/x-coredump",
+            _ => "application/x-executable",
+        };
+    }
+
+    if !is_text(head) {
+        return "application/octet-stream";
+    }
+    if let Some(interpreter) = head.strip_prefix(b"#!") {
+        let line = interpreter
+            .split(|&byte| byte == b'\n')
+            .next()
+            .unwrap_or_default();
+        let line = String::from_utf8_lossy(line);
+        if line.contains("python") {
+            return "text/x-script.python";
+        }
+        if line.contains("sh") {
+            return "text/x-shellscript";
+        }
+    }
+    let start = head.trim_ascii_start();
+    let start = start.get(..9).unwrap_or(start).to_ascii_lowercase();
+    if start.starts_with(b"<html") || start.starts_with(b"<!doctype") {
+        return "text/html";
+    }
+    "text/plain"
+}
+
+/// Whether `head` looks like UTF-8 text: no NUL bytes and no invalid sequences, apart from a
+/// character cut off at the end of what was read.
+fn is_text(head: &[u8]) -> bool {
+    if head.contains(&0) {
+        return false;
+    }
+    match std::str::from_utf8(head) {
+        Ok(_) => true,
+        // no error length means the input ended in the middle of a character
+        Err(err) => err.error_len().is_none() && head.len() == MAGIC_LEN,
+    }
+}
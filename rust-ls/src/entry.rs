@@ -0,0 +1,323 @@
+use std::{
+    ffi::OsString,
+    fs, io,
+    os::unix::fs::{FileTypeExt, MetadataExt},
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+
+use crate::{
+    content::{self, ContentOptions, Digest},
+    xattr::{Xattr, read_xattrs},
+};
+
+/// Which of a file's timestamps is shown and sorted by (`--time`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum TimeField {
+    /// last modification of the contents
+    #[default]
+    #[cfg_attr(feature = "cli", value(name = "mtime", alias = "modification"))]
+    Modification,
+    /// last access
+    #[cfg_attr(feature = "cli", value(name = "atime", alias = "access", alias = "use"))]
+    Access,
+    /// last change of the inode (status)
+    #[cfg_attr(feature = "cli", value(name = "ctime", alias = "status"))]
+    Change,
+    /// creation
+    #[cfg_attr(feature = "cli", value(name = "birth", alias = "creation"))]
+    Birth,
+}
+
+/// What a symbolic link leads to once the whole chain of links is followed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LinkState {
+    /// the chain ends in an existing file
+    Resolved,
+    /// some link in the chain points to a file that does not exist
+    Broken,
+    /// the chain runs in a circle, or is too long for the kernel to follow
+    Loop,
+}
+
+impl LinkState {
+    /// What following a chain of links with `stat` came to, which lets the kernel detect loops.
+    pub fn of(target: &io::Result<fs::Metadata>) -> Self {
+        match target {
+            Ok(_) => LinkState::Resolved,
+            Err(err) if err.raw_os_error() == Some(libc::ELOOP) => LinkState::Loop,
+            Err(_) => LinkState::Broken,
+        }
+    }
+
+    /// Lowercase name used in machine-readable output.
+    pub fn name(self) -> &'static str {
+        match self {
+            LinkState::Resolved => "resolved",
+            LinkState::Broken => "broken",
+            LinkState::Loop => "loop",
+        }
+    }
+}
+
+/// The file at the end of a chain of symbolic links, which the link is colored after.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LinkedFile {
+    pub kind: FileKind,
+    /// permission bits, including setuid, setgid and sticky
+    pub mode: u32,
+    pub nlink: u64,
+}
+
+impl LinkedFile {
+    fn from_metadata(metadata: &fs::Metadata) -> Self {
+        LinkedFile {
+            kind: FileKind::from_file_type(&metadata.file_type()),
+            mode: metadata.mode() & 0o7777,
+            nlink: metadata.nlink(),
+        }
+    }
+}
+
+/// Type of a file, as encoded in the first character of the long format.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FileKind {
+    File,
+    Directory,
+    Symlink,
+    BlockDevice,
+    CharDevice,
+    Fifo,
+    Socket,
+}
+
+impl FileKind {
+    pub fn from_file_type(file_type: &fs::FileType) -> Self {
+        if file_type.is_dir() {
+            FileKind::Directory
+        } else if file_type.is_symlink() {
+            FileKind::Symlink
+        } else if file_type.is_block_device() {
+            FileKind::BlockDevice
+        } else if file_type.is_char_device() {
+            FileKind::CharDevice
+        } else if file_type.is_fifo() {
+            FileKind::Fifo
+        } else if file_type.is_socket() {
+            FileKind::Socket
+        } else {
+            FileKind::File
+        }
+    }
+
+    /// `d`, `l`, `b`, `c`, `p`, `s` or `-`.
+    pub fn to_char(self) -> char {
+        match self {
+            FileKind::File => '-',
+            FileKind::Directory => 'd',
+            FileKind::Symlink => 'l',
+            FileKind::BlockDevice => 'b',
+            FileKind::CharDevice => 'c',
+            FileKind::Fifo => 'p',
+            FileKind::Socket => 's',
+        }
+    }
+
+    /// Lowercase name used in machine-readable output.
+    pub fn name(self) -> &'static str {
+        match self {
+            FileKind::File => "file",
+            FileKind::Directory => "directory",
+            FileKind::Symlink => "symlink",
+            FileKind::BlockDevice => "block_device",
+            FileKind::CharDevice => "char_device",
+            FileKind::Fifo => "fifo",
+            FileKind::Socket => "socket",
+        }
+    }
+
+    pub fn is_device(self) -> bool {
+        matches!(self, FileKind::BlockDevice | FileKind::CharDevice)
+    }
+}
+
+/// A listed file: the name to show for it, where it lives, and its parsed `lstat` metadata.
+///
+/// Everything a formatter needs is read once when the entry is created, so formatting and
+/// sorting never go back to the file system.
+#[derive(Clone, Debug)]
+pub struct Entry {
+    pub name: OsString,
+    pub path: PathBuf,
+    pub kind: FileKind,
+    /// permission bits, including setuid, setgid and sticky
+    pub mode: u32,
+    pub nlink: u64,
+    pub uid: u32,
+    pub gid: u32,
+    /// owner and group names recorded with the file, e.g. in an archive header; looked up from
+    /// `uid` and `gid` when `None`
+    pub user: Option<String>,
+    pub group: Option<String>,
+    pub size: u64,
+    /// allocated 512-byte blocks
+    pub blocks: u64,
+    pub ino: u64,
+    pub dev: u64,
+    /// device number, for block and character devices
+    pub rdev: u64,
+    pub accessed: Option<SystemTime>,
+    pub modified: Option<SystemTime>,
+    pub changed: Option<SystemTime>,
+    /// birth time, which std reads with `statx` on Linux; `None` if the file system lacks it
+    pub created: Option<SystemTime>,
+    /// where the entry points to, if it is a symbolic link
+    pub link_target: Option<PathBuf>,
+    /// whether the chain of links ends anywhere, if the entry is or was followed through a
+    /// symbolic link
+    pub link_state: Option<LinkState>,
+    /// what a symbolic link leads to, if the chain resolves
+    pub linked_file: Option<LinkedFile>,
+    /// extended attributes; empty unless read with [`Entry::read_xattrs`]
+    pub xattrs: Vec<Xattr>,
+    /// type guessed from the contents, if read with [`Entry::read_contents`]
+    pub mime_type: Option<&'static str>,
+    /// hash of the contents of a regular file, if read with [`Entry::read_contents`]
+    pub checksum: Option<Digest>,
+}
+
+impl Entry {
+    /// Reads the entry at `path` without following a final symbolic link.
+    pub fn new(name: impl Into<OsString>, path: PathBuf) -> io::Result<Self> {
+        let metadata = fs::symlink_metadata(&path)?;
+        Ok(Entry::from_metadata(name, path, &metadata))
+    }
+
+    /// Reads the entry at `path`, taking the metadata of the file at the end of the chain if it
+    /// is a symbolic link (`-L`). Broken and looping links keep their own metadata, with
+    /// [`Entry::link_state`] telling what went wrong.
+    pub fn followed(name: impl Into<OsString>, path: PathBuf) -> io::Result<Self> {
+        let metadata = fs::symlink_metadata(&path)?;
+        if !metadata.file_type().is_symlink() {
+            return Ok(Entry::from_metadata(name, path, &metadata));
+        }
+        match fs::metadata(&path) {
+            Ok(target) => {
+                let mut entry = Entry::from_metadata(name, path, &target);
+                entry.link_state = Some(LinkState::Resolved);
+                Ok(entry)
+            },
+            Err(_) => Ok(Entry::from_metadata(name, path, &metadata)),
+        }
+    }
+
+    pub fn from_metadata(
+        name: impl Into<OsString>,
+        path: PathBuf,
+        metadata: &fs::Metadata,
+    ) -> Self {
+        let kind = FileKind::from_file_type(&metadata.file_type());
+        let (link_target, link_state, linked_file) = if kind == FileKind::Symlink {
+            let target = fs::metadata(&path);
+            let linked_file = target.as_ref().ok().map(LinkedFile::from_metadata);
+            (fs::read_link(&path).ok(), Some(LinkState::of(&target)), linked_file)
+        } else {
+            (None, None, None)
+        };
+        // std has no accessor for the status change time
+        let changed = SystemTime::UNIX_EPOCH.checked_add(Duration::new(
+            metadata.ctime().max(0) as u64,
+            metadata.ctime_nsec() as u32,
+        ));
+
+        Entry {
+            name: name.into(),
+            path,
+            kind,
+            mode: metadata.mode() & 0o7777,
+            nlink: metadata.nlink(),
+            uid: metadata.uid(),
+            gid: metadata.gid(),
+            user: None,
+            group: None,
+            size: metadata.size(),
+            blocks: metadata.blocks(),
+            ino: metadata.ino(),
+            dev: metadata.dev(),
+            rdev: metadata.rdev(),
+            accessed: metadata.accessed().ok(),
+            modified: metadata.modified().ok(),
+            changed,
+            created: metadata.created().ok(),
+            link_target,
+            link_state,
+            linked_file,
+            xattrs: Vec::new(),
+            mime_type: None,
+            checksum: None,
+        }
+    }
+
+    /// Reads the extended attributes of the entry (not of a symbolic link's target). Files whose
+    /// attributes cannot be read are treated as having none.
+    pub fn read_xattrs(&mut self) {
+        self.xattrs = read_xattrs(&self.path).unwrap_or_default();
+    }
+
+    /// Reads what `options` asks for from the contents of the file. A followed symbolic link
+    /// (`-L`) is described by its target. Files that cannot be read get neither.
+    pub fn read_contents(&mut self, options: ContentOptions) {
+        if options.mime_type {
+            self.mime_type = content::mime_type(&self.path, self.kind, self.size);
+        }
+        if let Some(checksum) = options.checksum
+            && self.kind == FileKind::File
+        {
+            self.checksum = checksum.digest(&self.path).ok();
+        }
+    }
+
+    /// Whether the entry has a POSIX ACL beyond its permission bits.
+    pub fn has_acl(&self) -> bool {
+        self.xattrs.iter().any(Xattr::is_acl)
+    }
+
+    /// The SELinux label, e.g. `unconfined_u:object_r:user_home_t:s0`, if the entry has one.
+    pub fn security_context(&self) -> Option<String> {
+        let xattr = self
+            .xattrs
+            .iter()
+            .find(|xattr| xattr.is_security_context())?;
+        let label = xattr.value.strip_suffix(b"\0").unwrap_or(&xattr.value);
+        Some(String::from_utf8_lossy(label).into_owned())
+    }
+
+    pub fn time(&self, field: TimeField) -> Option<SystemTime> {
+        match field {
+            TimeField::Modification => self.modified,
+            TimeField::Access => self.accessed,
+            TimeField::Change => self.changed,
+            TimeField::Birth => self.created,
+        }
+    }
+
+    /// Whether the entry is a symbolic link that cannot be followed to an existing file.
+    pub fn is_dangling(&self) -> bool {
+        matches!(self.link_state, Some(LinkState::Broken | LinkState::Loop))
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.kind == FileKind::Directory
+    }
+
+    /// The `(dev, ino)` pair that every hard link to the same file shares.
+    pub fn file_id(&self) -> (u64, u64) {
+        (self.dev, self.ino)
+    }
+
+    /// Whether this is the implied `.` or `..` of a directory.
+    pub fn is_implied(&self) -> bool {
+        self.name == "." || self.name == ".."
+    }
+}
@@ -0,0 +1,180 @@
+use std::{
+    collections::HashMap,
+    ffi::{CString, OsStr},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use ignore::{
+    Match,
+    gitignore::{Gitignore, GitignoreBuilder},
+};
+
+use crate::entry::Entry;
+
+/// Kind of entries to keep (`--only-dirs`, `--only-files`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OnlyKind {
+    Directories,
+    Files,
+}
+
+/// Decides which entries of a directory are listed, on top of the dotfile rules of
+/// [`DirListing`](crate::DirListing).
+///
+/// Name patterns use shell globs as matched by `fnmatch(3)`, where a leading `.` must be matched
+/// explicitly, like GNU `ls -I`. Type and size filters leave the implied `.` and `..` alone, and
+/// size limits only apply to non-directories so that `-R` still descends everywhere.
+#[derive(Debug, Default)]
+pub struct EntryFilter {
+    ignore: Vec<CString>,
+    hide: Vec<CString>,
+    git_ignore: Option<GitIgnore>,
+    only: Option<OnlyKind>,
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+}
+
+impl EntryFilter {
+    /// Never lists entries matching one of `patterns` (`-I`, `--ignore`).
+    pub fn ignore<S: AsRef<str>>(mut self, patterns: &[S]) -> Self {
+        self.ignore.extend(to_patterns(patterns));
+        self
+    }
+
+    /// Leaves out entries matching one of `patterns` unless dotfiles are shown (`--hide`).
+    pub fn hide<S: AsRef<str>>(mut self, patterns: &[S]) -> Self {
+        self.hide.extend(to_patterns(patterns));
+        self
+    }
+
+    /// Leaves out entries that git ignores, following `.gitignore` files from the repository
+    /// root down to each listed directory, plus `.git/info/exclude`.
+    pub fn git_ignore(mut self, git_ignore: bool) -> Self {
+        self.git_ignore = git_ignore.then(GitIgnore::default);
+        self
+    }
+
+    pub fn only(mut self, only: Option<OnlyKind>) -> Self {
+        self.only = only;
+        self
+    }
+
+    /// Leaves out non-directories smaller than `min_size` or larger than `max_size` bytes.
+    pub fn size_range(mut self, min_size: Option<u64>, max_size: Option<u64>) -> Self {
+        self.min_size = min_size;
+        self.max_size = max_size;
+        self
+    }
+
+    /// Keeps the entries of the directory `dir` that pass every filter.
+    pub fn retain(&self, dir: &Path, entries: &mut Vec<Entry>, show_hidden: bool) {
+        let git_ignores = self
+            .git_ignore
+            .as_ref()
+            .and_then(|git_ignore| git_ignore.matchers(dir));
+        entries.retain(|entry| {
+            let name = entry.name.as_os_str();
+            if matches_any(&self.ignore, name) || (!show_hidden && matches_any(&self.hide, name)) {
+                return false;
+            }
+            if entry.is_implied() {
+                return true;
+            }
+            if let Some((dir, matchers)) = &git_ignores
+                && is_git_ignored(matchers, &dir.join(name), entry.is_dir())
+            {
+                return false;
+            }
+            match self.only {
+                Some(OnlyKind::Directories) if !entry.is_dir() => return false,
+                Some(OnlyKind::Files) if entry.is_dir() => return false,
+                _ => {},
+            }
+            entry.is_dir()
+                || (self.min_size.is_none_or(|min| entry.size >= min)
+                    && self.max_size.is_none_or(|max| entry.size <= max))
+        });
+    }
+}
+
+/// Patterns for `fnmatch`; command line arguments cannot contain NUL, so none are dropped there.
+fn to_patterns<S: AsRef<str>>(patterns: &[S]) -> impl Iterator<Item = CString> + '_ {
+    patterns
+        .iter()
+        .filter_map(|pattern| CString::new(pattern.as_ref()).ok())
+}
+
+fn matches_any(patterns: &[CString], name: &OsStr) -> bool {
+    if patterns.is_empty() {
+        return false;
+    }
+    let Ok(name) = CString::new(name.as_encoded_bytes()) else {
+        return false;
+    };
+    patterns.iter().any(|pattern| {
+        // SAFETY: both arguments are valid NUL-terminated strings
+        unsafe { libc::fnmatch(pattern.as_ptr(), name.as_ptr(), libc::FNM_PERIOD) == 0 }
+    })
+}
+
+/// The deepest `.gitignore` with an opinion about a name decides, so that a nested file can
+/// re-include (`!pattern`) what an outer one ignores.
+fn is_git_ignored(matchers: &[Arc<Gitignore>], path: &Path, is_dir: bool) -> bool {
+    matchers
+        .iter()
+        .map(|matcher| matcher.matched(path, is_dir))
+        .find(|decision| !decision.is_none())
+        .is_some_and(|decision| matches!(decision, Match::Ignore(_)))
+}
+
+/// Parsed ignore files, by the directory they apply to, so that each is read only once per run.
+#[derive(Debug, Default)]
+struct GitIgnore {
+    cache: Mutex<HashMap<PathBuf, Option<Arc<Gitignore>>>>,
+}
+
+impl GitIgnore {
+    /// The canonical path of `dir`, to match entry paths under, and the matchers that apply to
+    /// its entries, deepest first. Directories outside of a git repository have none.
+    fn matchers(&self, dir: &Path) -> Option<(PathBuf, Vec<Arc<Gitignore>>)> {
+        let dir = dir.canonicalize().ok()?;
+        let root = dir
+            .ancestors()
+            .find(|ancestor| ancestor.join(".git").exists())?;
+
+        let mut cache = self
+            .cache
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut matchers = Vec::new();
+        for ancestor in dir.ancestors() {
+            let matcher = cache
+                .entry(ancestor.to_path_buf())
+                .or_insert_with(|| load(ancestor, &ancestor.join(".gitignore")));
+            matchers.extend(matcher.clone());
+            if ancestor == root {
+                break;
+            }
+        }
+
+        // the repository's own exclude file has the lowest precedence
+        let exclude = root.join(".git/info/exclude");
+        let matcher = cache
+            .entry(exclude.clone())
+            .or_insert_with(|| load(root, &exclude));
+        matchers.extend(matcher.clone());
+        Some((dir, matchers))
+    }
+}
+
+/// Reads the ignore file at `path` with patterns relative to `root`, if it exists and has any.
+fn load(root: &Path, path: &Path) -> Option<Arc<Gitignore>> {
+    if !path.is_file() {
+        return None;
+    }
+    let mut builder = GitignoreBuilder::new(root);
+    builder.add(path);
+    let matcher = builder.build().ok()?;
+    (!matcher.is_empty()).then(|| Arc::new(matcher))
+}
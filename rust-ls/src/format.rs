@@ -0,0 +1,235 @@
+use std::{sync::Arc, time::SystemTime};
+
+use crate::{
+    block_size::BlockSize,
+    content::ContentOptions,
+    entry::{Entry, FileKind, TimeField},
+    git::GitCache,
+    owner::{group_name, user_name},
+    quoting::Quoting,
+    time_style::TimeStyle,
+};
+
+/// Turns a byte count into the text of the size column. Formatters are shared between threads,
+/// e.g. by a server that formats listings for many requests.
+pub trait SizeFormatter: Send + Sync {
+    fn format_size(&self, size: u64) -> String;
+}
+
+/// Turns a timestamp into the text of the time column.
+pub trait TimeFormatter: Send + Sync {
+    fn format_time(&self, time: SystemTime) -> String;
+}
+
+/// Turns a whole entry into one line of output. Lines are bytes, since names need not be UTF-8.
+pub trait EntryFormatter: Send + Sync {
+    fn format_entry(&self, entry: &Entry) -> Vec<u8>;
+}
+
+/// The `ls -l` line: type and permissions, link count, owner, group, size, time and name.
+///
+/// Names and symlink targets are written with `quoting`; callers that decorate them (e.g. with colors)
+/// can use [`LongFormatter::format_columns`] and append the name themselves.
+pub struct LongFormatter {
+    pub size: Box<dyn SizeFormatter>,
+    pub time: Box<dyn TimeFormatter>,
+    /// which timestamp goes in the time column
+    pub time_field: TimeField,
+    /// how names and symlink targets are written
+    pub quoting: Quoting,
+    /// where to look up the git status column (`--git`), if it is shown
+    pub git: Option<Arc<GitCache>>,
+    /// whether to show the security context column after the group (`-Z`)
+    pub context: bool,
+    /// which columns read from file contents to show before the name (`--checksum`, `--mime`)
+    pub contents: ContentOptions,
+    /// which of the columns every long listing has are shown
+    pub columns: Columns,
+}
+
+/// The columns of the long format that are always there unless hidden, e.g. from the browser.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Columns {
+    /// type and permissions
+    pub mode: bool,
+    /// hard link count
+    pub links: bool,
+    pub owner: bool,
+    pub group: bool,
+    /// size, or device number
+    pub size: bool,
+    pub time: bool,
+}
+
+impl Default for Columns {
+    fn default() -> Self {
+        Columns { mode: true, links: true, owner: true, group: true, size: true, time: true }
+    }
+}
+
+impl Default for LongFormatter {
+    fn default() -> Self {
+        LongFormatter {
+            size: Box::new(BlockSize::bytes()),
+            time: Box::new(TimeStyle::default()),
+            time_field: TimeField::Modification,
+            quoting: Quoting::default(),
+            git: None,
+            context: false,
+            contents: ContentOptions::default(),
+            columns: Columns::default(),
+        }
+    }
+}
+
+impl LongFormatter {
+    /// Every column before the name.
+    pub fn format_columns(&self, entry: &Entry) -> String {
+        // file type and permissions, marked when there is more to the access rights or metadata
+        let file_type_char = entry.kind.to_char();
+        let mode = to_permission_expression(entry.mode);
+        let mode = format!("{}{}{}", file_type_char, mode, to_mode_suffix(entry));
+
+        // owner and group
+        let user_name = entry
+            .user
+            .clone()
+            .or_else(|| user_name(entry.uid))
+            .unwrap_or(entry.uid.to_string());
+        let group_name = entry
+            .group
+            .clone()
+            .or_else(|| group_name(entry.gid))
+            .unwrap_or(entry.gid.to_string());
+
+        // file size (device number for devices) and the selected timestamp
+        let file_size = if entry.kind.is_device() {
+            to_device_number(entry.rdev)
+        } else {
+            self.size.format_size(entry.size)
+        };
+        let time = match entry.time(self.time_field) {
+            Some(time) => self.time.format_time(time),
+            // e.g. a birth time the file system does not record
+            None => "?".to_string(),
+        };
+
+        // mode and link count share a field, so that the count lines up right after the mode
+        let mut fields = Vec::new();
+        match (self.columns.mode, self.columns.links) {
+            (true, true) => fields.push(format!("{:<11}{:>4}", mode, entry.nlink)),
+            (true, false) => fields.push(format!("{:<11}", mode)),
+            (false, true) => fields.push(format!("{:>4}", entry.nlink)),
+            (false, false) => {},
+        }
+        if self.columns.owner {
+            fields.push(format!("{:<8}", user_name));
+        }
+        if self.columns.group {
+            fields.push(format!("{:<8}", group_name));
+        }
+        if self.context {
+            let context = entry.security_context();
+            fields.push(context.unwrap_or_else(|| "?".to_string()));
+        }
+        if self.columns.size {
+            fields.push(format!("{:>4}", file_size));
+        }
+        if self.columns.time {
+            fields.push(time);
+        }
+
+        // staged and unstaged changes, blank outside of a repository so that it cannot be taken
+        // for an unmodified file (`--`)
+        if let Some(git) = &self.git {
+            let status = git.status(entry).map(|status| status.to_column());
+            fields.push(status.unwrap_or_else(|| "  ".to_string()));
+        }
+
+        // `-` for entries that have no contents to hash, `?` when they could not be read
+        if self.contents.checksum.is_some() {
+            let checksum = match &entry.checksum {
+                Some(digest) => digest.hex.as_str(),
+                None if entry.kind == FileKind::File => "?",
+                None => "-",
+            };
+            fields.push(format!("{:<64}", checksum));
+        }
+        if self.contents.mime_type {
+            fields.push(format!("{:<24}", entry.mime_type.unwrap_or("?")));
+        }
+        fields.join(" ")
+    }
+}
+
+impl EntryFormatter for LongFormatter {
+    fn format_entry(&self, entry: &Entry) -> Vec<u8> {
+        let mut line = self.format_columns(entry).into_bytes();
+        line.push(b' ');
+        // file name (with symlink target if applicable)
+        line.extend(self.quoting.quote(&entry.name));
+        if let Some(target) = &entry.link_target {
+            line.extend_from_slice(b" -> ");
+            line.extend(self.quoting.quote(target.as_os_str()));
+        }
+        line
+    }
+}
+
+pub fn to_permission_expression(mode: u32) -> String {
+    let chars = ['r', 'w', 'x'];
+    let mut result = String::with_capacity(9);
+    for i in (0..9).rev() {
+        if (mode >> i) & 1 == 1 {
+            result.push(chars[2 - (i % 3)]);
+        } else {
+            result.push('-');
+        }
+    }
+
+    // setuid, setgid and sticky bits take the place of the matching execute bit:
+    // lowercase when that execute bit is set too, uppercase when it is not
+    let special_bits = [(libc::S_ISUID, 2, 's'), (libc::S_ISGID, 5, 's'), (libc::S_ISVTX, 8, 't')];
+    for (bit, index, letter) in special_bits {
+        if mode & bit != 0 {
+            let letter =
+                if result.as_bytes()[index] == b'x' { letter } else { letter.to_ascii_uppercase() };
+            result.replace_range(index..=index, letter.encode_utf8(&mut [0; 4]));
+        }
+    }
+    result
+}
+
+/// `+` after the mode when the entry has an ACL, `@` when it has other extended attributes.
+///
+/// SELinux labels do not count, since on a system that uses them every file has one. Entries
+/// whose attributes were not read get no marker.
+pub fn to_mode_suffix(entry: &Entry) -> &'static str {
+    if entry.has_acl() {
+        "+"
+    } else if entry
+        .xattrs
+        .iter()
+        .any(|xattr| !xattr.is_security_context())
+    {
+        "@"
+    } else {
+        ""
+    }
+}
+
+/// `major, minor` of a block or character device, shown in place of the size.
+pub fn to_device_number(rdev: u64) -> String {
+    format!("{:>3}, {:>3}", libc::major(rdev), libc::minor(rdev))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn long_formatter_can_be_shared_between_threads() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<LongFormatter>();
+    }
+}
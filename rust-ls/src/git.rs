@@ -0,0 +1,233 @@
+use std::{
+    collections::HashMap,
+    ffi::OsStr,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use git2::{Repository, Status, StatusOptions};
+
+use crate::entry::Entry;
+
+/// A change to a path on one side of the index, ordered from least to most noteworthy so that a
+/// directory can show the most noteworthy change among its contents.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Change {
+    Unmodified,
+    Ignored,
+    New,
+    Renamed,
+    TypeChange,
+    Deleted,
+    Modified,
+    Conflicted,
+}
+
+impl Change {
+    /// `-`, `I`, `N`, `R`, `T`, `D`, `M` or `U`.
+    pub fn to_char(self) -> char {
+        match self {
+            Change::Unmodified => '-',
+            Change::Ignored => 'I',
+            Change::New => 'N',
+            Change::Renamed => 'R',
+            Change::TypeChange => 'T',
+            Change::Deleted => 'D',
+            Change::Modified => 'M',
+            Change::Conflicted => 'U',
+        }
+    }
+}
+
+/// Status of a path against the index (`staged`) and of the work tree against the index
+/// (`unstaged`). Untracked files are new on the unstaged side.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GitStatus {
+    pub staged: Change,
+    pub unstaged: Change,
+}
+
+impl GitStatus {
+    const UNMODIFIED: GitStatus =
+        GitStatus { staged: Change::Unmodified, unstaged: Change::Unmodified };
+
+    fn from_status(status: Status) -> Self {
+        if status.is_conflicted() {
+            return GitStatus { staged: Change::Conflicted, unstaged: Change::Conflicted };
+        }
+        let staged = if status.is_index_new() {
+            Change::New
+        } else if status.is_index_modified() {
+            Change::Modified
+        } else if status.is_index_deleted() {
+            Change::Deleted
+        } else if status.is_index_renamed() {
+            Change::Renamed
+        } else if status.is_index_typechange() {
+            Change::TypeChange
+        } else {
+            Change::Unmodified
+        };
+        let unstaged = if status.is_wt_new() {
+            Change::New
+        } else if status.is_wt_modified() {
+            Change::Modified
+        } else if status.is_wt_deleted() {
+            Change::Deleted
+        } else if status.is_wt_renamed() {
+            Change::Renamed
+        } else if status.is_wt_typechange() {
+            Change::TypeChange
+        } else if status.is_ignored() {
+            Change::Ignored
+        } else {
+            Change::Unmodified
+        };
+        GitStatus { staged, unstaged }
+    }
+
+    /// The two-letter column of the long format, e.g. `-M` or `N-`.
+    pub fn to_column(self) -> String {
+        [self.staged.to_char(), self.unstaged.to_char()]
+            .iter()
+            .collect()
+    }
+}
+
+/// Statuses of every changed path of one repository, read once from its index and work tree.
+#[derive(Debug)]
+struct RepoStatuses {
+    /// `(path relative to the work tree, status)`, sorted by path; untracked and ignored
+    /// directories are single entries ending in `/`
+    statuses: Vec<(Vec<u8>, GitStatus)>,
+}
+
+impl RepoStatuses {
+    fn read(repo: &Repository) -> Option<Self> {
+        let mut options = StatusOptions::new();
+        options
+            .include_untracked(true)
+            .include_ignored(true)
+            .recurse_untracked_dirs(false)
+            .recurse_ignored_dirs(false)
+            .exclude_submodules(true)
+            .renames_head_to_index(true);
+        let mut statuses: Vec<_> = repo
+            .statuses(Some(&mut options))
+            .ok()?
+            .iter()
+            .map(|entry| (entry.path_bytes().to_vec(), GitStatus::from_status(entry.status())))
+            .collect();
+        statuses.sort_by(|(a, _), (b, _)| a.cmp(b));
+        Some(RepoStatuses { statuses })
+    }
+
+    /// The status of the file at `path`, or for a directory the most noteworthy status on each
+    /// side among its contents. Ignored contents do not count towards a directory that is not
+    /// ignored itself.
+    fn status(&self, path: &[u8], is_dir: bool) -> GitStatus {
+        // everything inside an untracked or ignored directory shares its status
+        for (index, _) in path.iter().enumerate().filter(|(_, byte)| **byte == b'/') {
+            if let Some(status) = self.lookup(&path[..=index]) {
+                return status;
+            }
+        }
+        if !is_dir {
+            return self.lookup(path).unwrap_or(GitStatus::UNMODIFIED);
+        }
+
+        let mut prefix = path.to_vec();
+        if !prefix.is_empty() {
+            prefix.push(b'/');
+        }
+        if let Some(status) = self.lookup(&prefix) {
+            // the whole directory is untracked or ignored
+            return status;
+        }
+        let start = self
+            .statuses
+            .partition_point(|(entry, _)| entry.as_slice() < prefix.as_slice());
+        self.statuses[start..]
+            .iter()
+            .take_while(|(entry, _)| entry.starts_with(&prefix))
+            .map(|(_, status)| *status)
+            .filter(|status| status.unstaged != Change::Ignored)
+            .fold(GitStatus::UNMODIFIED, |rollup, status| GitStatus {
+                staged: rollup.staged.max(status.staged),
+                unstaged: rollup.unstaged.max(status.unstaged),
+            })
+    }
+
+    fn lookup(&self, path: &[u8]) -> Option<GitStatus> {
+        self.statuses
+            .binary_search_by(|(entry, _)| entry.as_slice().cmp(path))
+            .ok()
+            .map(|index| self.statuses[index].1)
+    }
+}
+
+/// A directory's repository, and the directory's path relative to the work tree.
+type RepoDirectory = (Arc<RepoStatuses>, PathBuf);
+
+/// Repositories discovered so far, by the directories that were asked about, so that each
+/// repository's status is read once however many directories of it are listed.
+#[derive(Debug, Default)]
+pub struct GitCache {
+    /// listed directory → its repository
+    directories: Mutex<HashMap<PathBuf, Option<RepoDirectory>>>,
+    /// work tree → statuses
+    repos: Mutex<HashMap<PathBuf, Option<Arc<RepoStatuses>>>>,
+}
+
+impl GitCache {
+    /// The git status of `entry`, or `None` if it is not inside a git work tree.
+    pub fn status(&self, entry: &Entry) -> Option<GitStatus> {
+        // the implied `.` and `..` are looked up as directories of their own
+        let (dir, name) = if entry.is_implied() {
+            (entry.path.as_path(), None)
+        } else {
+            // a bare file operand such as `Cargo.toml` has an empty parent
+            let parent = entry.path.parent()?;
+            let parent = if parent.as_os_str().is_empty() { Path::new(".") } else { parent };
+            (parent, Some(entry.name.as_os_str()))
+        };
+        let (repo, relative_dir) = self.directory(dir)?;
+        let relative = match name {
+            Some(name) => relative_dir.join(name),
+            None => relative_dir,
+        };
+        Some(repo.status(relative.as_os_str().as_encoded_bytes(), entry.is_dir()))
+    }
+
+    fn directory(&self, dir: &Path) -> Option<RepoDirectory> {
+        let mut directories = self
+            .directories
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        directories
+            .entry(dir.to_path_buf())
+            .or_insert_with(|| self.discover(dir))
+            .clone()
+    }
+
+    fn discover(&self, dir: &Path) -> Option<RepoDirectory> {
+        let dir = dir.canonicalize().ok()?;
+        let repo = Repository::discover(&dir).ok()?;
+        let workdir = repo.workdir()?.canonicalize().ok()?;
+        let relative_dir = dir.strip_prefix(&workdir).ok()?.to_path_buf();
+        // the .git directory itself is not part of the work tree
+        if relative_dir.starts_with(OsStr::new(".git")) {
+            return None;
+        }
+
+        let mut repos = self
+            .repos
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let statuses = repos
+            .entry(workdir)
+            .or_insert_with(|| RepoStatuses::read(&repo).map(Arc::new))
+            .clone()?;
+        Some((statuses, relative_dir))
+    }
+}
@@ -0,0 +1,126 @@
+use std::{
+    env,
+    io::{self, IsTerminal, Write},
+    mem,
+};
+
+/// Separator between two columns, in terminal cells.
+const COLUMN_SEPARATOR: usize = 2;
+
+/// Narrowest possible column: a one-cell name plus the separator.
+const MIN_COLUMN_WIDTH: usize = 1 + COLUMN_SEPARATOR;
+
+/// A piece of text to place in the grid, along with how many terminal cells it takes up.
+///
+/// The width is kept apart from the text because it is not the byte or char count: CJK and emoji
+/// take two cells, and escape sequences take none. The text is bytes, since names need not be
+/// UTF-8.
+pub struct Cell {
+    pub text: Vec<u8>,
+    pub width: usize,
+}
+
+impl Cell {
+    pub fn new(text: Vec<u8>) -> Self {
+        let width = display_width(&text);
+        Cell { text, width }
+    }
+}
+
+/// How many terminal cells `text` takes up. Bytes that are not UTF-8 take one each, as the
+/// replacement character a terminal shows for them.
+pub fn display_width(text: &[u8]) -> usize {
+    text.utf8_chunks()
+        .map(|chunk| unicode_width::UnicodeWidthStr::width(chunk.valid()) + chunk.invalid().len())
+        .sum()
+}
+
+/// Writes `line` and a newline to stdout as they are. `println!` only takes text, which would
+/// lose the bytes of names that are not UTF-8.
+pub fn print_line(line: &[u8]) {
+    let mut stdout = io::stdout().lock();
+    if let Err(err) = stdout
+        .write_all(line)
+        .and_then(|()| stdout.write_all(b"\n"))
+    {
+        panic!("failed printing to stdout: {}", err);
+    }
+}
+
+/// Width of the output line: the terminal width when stdout is a tty, then `$COLUMNS`, and 80
+/// as a last resort.
+pub fn terminal_width() -> usize {
+    if std::io::stdout().is_terminal() {
+        // SAFETY: `winsize` is a plain C struct of integers, for which all zeros is valid
+        let mut winsize: libc::winsize = unsafe { mem::zeroed() };
+        // SAFETY: TIOCGWINSZ only writes a `winsize` through the pointer, which is valid and
+        // exclusively borrowed for the call
+        let ret = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut winsize) };
+        if ret == 0 && winsize.ws_col > 0 {
+            return winsize.ws_col as usize;
+        }
+    }
+    env::var("COLUMNS")
+        .ok()
+        .and_then(|columns| columns.parse().ok())
+        .filter(|&w| w > 0)
+        .unwrap_or(80)
+}
+
+/// Prints `cells` in as many columns as fit in `line_width`, like GNU `ls -C` (top to bottom) or
+/// `ls -x` (left to right when `across` is set).
+///
+/// A `line_width` of 0 means there is no limit.
+pub fn print_grid(cells: &[Cell], line_width: usize, across: bool) {
+    if cells.is_empty() {
+        return;
+    }
+    let line_width = if line_width == 0 { usize::MAX } else { line_width };
+    let (rows, column_widths) = layout(cells, line_width, across);
+    let columns = column_widths.len();
+
+    for row in 0..rows {
+        let mut line = Vec::new();
+        for (column, column_width) in column_widths.iter().enumerate() {
+            let index = if across { row * columns + column } else { column * rows + row };
+            let Some(cell) = cells.get(index) else {
+                break;
+            };
+            line.extend_from_slice(&cell.text);
+
+            // no padding after the last cell of a row
+            let next = if across { index + 1 } else { index + rows };
+            if column + 1 < columns && next < cells.len() {
+                line.extend(std::iter::repeat_n(b' ', column_width - cell.width));
+            }
+        }
+        print_line(&line);
+    }
+}
+
+/// Finds the largest number of columns whose total width fits in `line_width`.
+///
+/// Every column is as wide as its widest cell plus the separator, except the last one, which
+/// needs no separator. Returns the number of rows and the width of each column.
+fn layout(cells: &[Cell], line_width: usize, across: bool) -> (usize, Vec<usize>) {
+    let max_columns = (line_width / MIN_COLUMN_WIDTH).clamp(1, cells.len());
+
+    for columns in (2..=max_columns).rev() {
+        let rows = cells.len().div_ceil(columns);
+        // filling top to bottom may need fewer columns than asked for
+        let columns = if across { columns } else { cells.len().div_ceil(rows) };
+
+        let mut column_widths = vec![0; columns];
+        for (index, cell) in cells.iter().enumerate() {
+            let column = if across { index % columns } else { index / rows };
+            column_widths[column] = column_widths[column].max(cell.width + COLUMN_SEPARATOR);
+        }
+        let total_width = column_widths.iter().sum::<usize>() - COLUMN_SEPARATOR;
+        if total_width <= line_width {
+            return (rows, column_widths);
+        }
+    }
+
+    let widest = cells.iter().map(|cell| cell.width).max().unwrap_or(0);
+    (cells.len(), vec![widest])
+}
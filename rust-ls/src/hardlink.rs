@@ -0,0 +1,23 @@
+use std::collections::HashMap;
+
+use crate::entry::Entry;
+
+/// Numbers the files that have several hard links (`--group-hardlinks`), so that every name of
+/// the same file gets the same number wherever it is listed during a run.
+#[derive(Debug, Default)]
+pub struct HardLinkGroups {
+    groups: HashMap<(u64, u64), usize>,
+}
+
+impl HardLinkGroups {
+    /// The group of `entry`, numbered from 1 in the order files are first seen, or `None` if it
+    /// has a single link. Directories are left out, since their link count comes from their
+    /// subdirectories.
+    pub fn group(&mut self, entry: &Entry) -> Option<usize> {
+        if entry.is_dir() || entry.nlink < 2 {
+            return None;
+        }
+        let next = self.groups.len() + 1;
+        Some(*self.groups.entry(entry.file_id()).or_insert(next))
+    }
+}
@@ -0,0 +1,113 @@
+use std::time::SystemTime;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::Serialize;
+
+use crate::{
+    entry::{Entry, LinkState},
+    format::{EntryFormatter, to_permission_expression},
+    owner::{group_name, user_name},
+};
+
+/// One entry of `--format=json` / `--format=ndjson`.
+///
+/// Names are given both as raw bytes, which are exact, and as a lossy UTF-8 string, which is
+/// convenient but may contain U+FFFD for names that are not valid UTF-8. Timestamps are RFC 3339
+/// in UTC with nanoseconds.
+#[derive(Debug, Serialize)]
+pub struct Record {
+    name: String,
+    name_bytes: Vec<u8>,
+    path: String,
+    #[serde(rename = "type")]
+    file_type: &'static str,
+    /// permission bits, including setuid, setgid and sticky
+    mode: u32,
+    /// the mode string of the long format, e.g. `drwxr-xr-x`
+    permissions: String,
+    nlink: u64,
+    uid: u32,
+    user: Option<String>,
+    gid: u32,
+    group: Option<String>,
+    size: u64,
+    /// allocated 512-byte blocks
+    blocks: u64,
+    inode: u64,
+    device: Option<DeviceNumber>,
+    accessed: Option<String>,
+    modified: Option<String>,
+    changed: Option<String>,
+    created: Option<String>,
+    symlink_target: Option<String>,
+    /// `resolved`, `broken` or `loop` for symbolic links
+    symlink_state: Option<&'static str>,
+    /// with `--mime`
+    mime_type: Option<&'static str>,
+    /// `algorithm:hex`, with `--checksum`
+    checksum: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct DeviceNumber {
+    major: u32,
+    minor: u32,
+}
+
+impl Record {
+    pub fn new(entry: &Entry) -> Self {
+        let device = entry.kind.is_device().then(|| DeviceNumber {
+            major: libc::major(entry.rdev),
+            minor: libc::minor(entry.rdev),
+        });
+
+        Record {
+            name: entry.name.to_string_lossy().into_owned(),
+            name_bytes: entry.name.as_encoded_bytes().to_vec(),
+            path: entry.path.to_string_lossy().into_owned(),
+            file_type: entry.kind.name(),
+            mode: entry.mode,
+            permissions: format!(
+                "{}{}",
+                entry.kind.to_char(),
+                to_permission_expression(entry.mode)
+            ),
+            nlink: entry.nlink,
+            uid: entry.uid,
+            user: entry.user.clone().or_else(|| user_name(entry.uid)),
+            gid: entry.gid,
+            group: entry.group.clone().or_else(|| group_name(entry.gid)),
+            size: entry.size,
+            blocks: entry.blocks,
+            inode: entry.ino,
+            device,
+            accessed: entry.accessed.map(to_rfc3339),
+            modified: entry.modified.map(to_rfc3339),
+            changed: entry.changed.map(to_rfc3339),
+            created: entry.created.map(to_rfc3339),
+            symlink_target: entry
+                .link_target
+                .as_ref()
+                .map(|target| target.to_string_lossy().into_owned()),
+            symlink_state: entry.link_state.map(LinkState::name),
+            mime_type: entry.mime_type,
+            checksum: entry.checksum.as_ref().map(ToString::to_string),
+        }
+    }
+}
+
+/// One NDJSON line per entry.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct JsonFormatter;
+
+impl EntryFormatter for JsonFormatter {
+    fn format_entry(&self, entry: &Entry) -> Vec<u8> {
+        // a record holds nothing but strings and numbers, which always serialize
+        serde_json::to_vec(&Record::new(entry)).unwrap_or_default()
+    }
+}
+
+/// RFC 3339 in UTC with nanoseconds, as records and snapshots write timestamps.
+pub fn to_rfc3339(time: SystemTime) -> String {
+    DateTime::<Utc>::from(time).to_rfc3339_opts(SecondsFormat::Nanos, true)
+}
@@ -0,0 +1,40 @@
+//! The listing and formatting code behind the `rust-ls` binary.
+//!
+//! [`DirListing`] reads a directory into [`Entry`] values, which hold the parsed metadata of each
+//! file. The [`format`] module turns entries into the text of `ls -l`, either as a whole line with
+//! [`LongFormatter`] or column by column through the [`SizeFormatter`] and [`TimeFormatter`]
+//! traits, and [`json`] turns them into machine-readable records.
+
+pub mod archive;
+pub mod block_size;
+pub mod color;
+pub mod content;
+pub mod entry;
+pub mod filter;
+pub mod format;
+pub mod git;
+pub mod grid;
+pub mod hardlink;
+pub mod json;
+pub mod listing;
+pub mod owner;
+pub mod quoting;
+pub mod snapshot;
+pub mod sort;
+pub mod time_style;
+pub mod usage;
+pub mod watch;
+pub mod xattr;
+
+pub use crate::{
+    block_size::BlockSize,
+    content::{Checksum, ContentOptions},
+    entry::{Entry, FileKind, LinkState, TimeField},
+    filter::EntryFilter,
+    format::{EntryFormatter, LongFormatter, SizeFormatter, TimeFormatter},
+    listing::{DirListing, ListingError},
+    quoting::{Quoting, QuotingStyle},
+    snapshot::Snapshot,
+    sort::{SortKey, SortOptions},
+    time_style::TimeStyle,
+};
@@ -0,0 +1,199 @@
+use std::{fs, io, path::PathBuf, sync::Arc};
+
+use rayon::prelude::*;
+
+use crate::{
+    content::ContentOptions,
+    entry::Entry,
+    filter::EntryFilter,
+    sort::{SortOptions, sort_entries},
+    usage::DirectoryTotals,
+};
+
+/// Reads the entries of one directory.
+///
+/// ```no_run
+/// use rust_ls::{DirListing, SortKey, SortOptions};
+///
+/// let entries = DirListing::new("/etc")
+///     .almost_all(true)
+///     .sort(SortOptions { key: SortKey::Size, ..SortOptions::default() })
+///     .read()?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct DirListing {
+    path: PathBuf,
+    all: bool,
+    almost_all: bool,
+    sort: SortOptions,
+    filter: Arc<EntryFilter>,
+    xattrs: bool,
+    contents: ContentOptions,
+    totals: Option<Arc<DirectoryTotals>>,
+    dereference: bool,
+}
+
+impl DirListing {
+    /// A listing of `path` that hides dotfiles and sorts by name, like plain `ls`.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        DirListing {
+            path: path.into(),
+            all: false,
+            almost_all: false,
+            sort: SortOptions::default(),
+            filter: Arc::default(),
+            xattrs: false,
+            contents: ContentOptions::default(),
+            totals: None,
+            dereference: false,
+        }
+    }
+
+    /// Includes dotfiles along with the implied `.` and `..` (`ls -a`).
+    pub fn all(mut self, all: bool) -> Self {
+        self.all = all;
+        self
+    }
+
+    /// Includes dotfiles, but not `.` and `..` (`ls -A`).
+    pub fn almost_all(mut self, almost_all: bool) -> Self {
+        self.almost_all = almost_all;
+        self
+    }
+
+    pub fn sort(mut self, sort: SortOptions) -> Self {
+        self.sort = sort;
+        self
+    }
+
+    /// Leaves out entries as decided by `filter`, which can be shared by many listings.
+    pub fn filter(mut self, filter: Arc<EntryFilter>) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Also reads the extended attributes of every entry, for ACL markers, security contexts
+    /// and `-@`.
+    pub fn xattrs(mut self, xattrs: bool) -> Self {
+        self.xattrs = xattrs;
+        self
+    }
+
+    /// Also reads the contents of every entry for the MIME type and checksum columns. Like the
+    /// `lstat` calls, this runs on the rayon thread pool, so large files are hashed in parallel.
+    pub fn contents(mut self, contents: ContentOptions) -> Self {
+        self.contents = contents;
+        self
+    }
+
+    /// Shows directories with the recursive size of what they hold (`--du`), as long as `totals`
+    /// walked them. Sorting by size then goes by these totals.
+    pub fn totals(mut self, totals: Option<Arc<DirectoryTotals>>) -> Self {
+        self.totals = totals;
+        self
+    }
+
+    /// Describes symbolic links by the files they lead to (`ls -L`).
+    pub fn dereference(mut self, dereference: bool) -> Self {
+        self.dereference = dereference;
+        self
+    }
+
+    /// Reads the directory, leaving out entries that cannot be read. Use
+    /// [`DirListing::read_with_errors`] to find out about them.
+    pub fn read(&self) -> io::Result<Vec<Entry>> {
+        self.read_with_errors().map(|(entries, _)| entries)
+    }
+
+    /// Reads the directory, along with whatever could not be read in it. Only a directory that
+    /// cannot be opened at all is an error; problems with single entries, such as a file removed
+    /// while the directory is listed, leave that entry out and are returned next to the others.
+    ///
+    /// The entries are `lstat`ed on the rayon thread pool, which hides the latency of slow (e.g.
+    /// network) file systems. They come back in directory order before being sorted, so the
+    /// result does not depend on which worker finished first.
+    pub fn read_with_errors(&self) -> io::Result<(Vec<Entry>, Vec<ListingError>)> {
+        let mut entries = Vec::new();
+        let mut errors = Vec::new();
+
+        // implied . and .. for -a
+        if self.all {
+            for name in [".", ".."] {
+                let path = self.path.join(name);
+                match Entry::new(name, path.clone()) {
+                    Ok(entry) => entries.push(self.complete(entry)),
+                    Err(err) => errors.push(ListingError::Entry(path, err)),
+                }
+            }
+        }
+
+        // read directory, skipping dotfiles unless -a or -A is given
+        let show_hidden = self.all || self.almost_all;
+        let mut dir_entries = Vec::new();
+        for dir_entry in fs::read_dir(&self.path)? {
+            match dir_entry {
+                Ok(dir_entry)
+                    if show_hidden
+                        || !dir_entry.file_name().as_encoded_bytes().starts_with(b".") =>
+                {
+                    dir_entries.push(dir_entry)
+                },
+                Ok(_) => {},
+                Err(err) => {
+                    // a failing readdir(3) keeps failing, so nothing more is to be had
+                    errors.push(ListingError::ReadDir(err));
+                    break;
+                },
+            }
+        }
+        let read_entries: Vec<_> = dir_entries
+            .into_par_iter()
+            .map(|dir_entry| {
+                let entry = if self.dereference {
+                    Entry::followed(dir_entry.file_name(), dir_entry.path())
+                } else {
+                    dir_entry.metadata().map(|metadata| {
+                        Entry::from_metadata(dir_entry.file_name(), dir_entry.path(), &metadata)
+                    })
+                };
+                entry
+                    .map(|entry| self.complete(entry))
+                    .map_err(|err| ListingError::Entry(dir_entry.path(), err))
+            })
+            .collect();
+        for entry in read_entries {
+            match entry {
+                Ok(entry) => entries.push(entry),
+                Err(err) => errors.push(err),
+            }
+        }
+        self.filter.retain(&self.path, &mut entries, show_hidden);
+
+        sort_entries(&mut entries, &self.sort);
+        Ok((entries, errors))
+    }
+
+    /// Reads what else was asked for about an entry.
+    fn complete(&self, mut entry: Entry) -> Entry {
+        if self.xattrs {
+            entry.read_xattrs();
+        }
+        if !self.contents.is_empty() {
+            entry.read_contents(self.contents);
+        }
+        if let Some(totals) = &self.totals {
+            totals.apply(&mut entry);
+        }
+        entry
+    }
+}
+
+/// Something that is missing from a listing, without keeping the rest from being listed.
+#[derive(Debug)]
+pub enum ListingError {
+    /// the entry at this path could not be read, e.g. because it was removed in the meantime
+    Entry(PathBuf, io::Error),
+    /// reading the directory failed part way through, so entries may be missing
+    ReadDir(io::Error),
+}
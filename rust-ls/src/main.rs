@@ -1,117 +1,1019 @@
+mod browse;
+mod cli;
+
 use std::{
-    env,
-    fs::{self},
-    os::unix::fs::{MetadataExt, PermissionsExt},
+    collections::{HashMap, HashSet},
+    ffi::{OsStr, OsString},
+    fs::{self, File},
+    io::{self, IsTerminal},
+    num::NonZeroUsize,
+    os::unix::{ffi::OsStrExt, fs::MetadataExt},
+    path::{Path, PathBuf},
+    process,
+    sync::Arc,
+    time::{Duration, SystemTime},
 };
 
-use chrono::{DateTime, Local};
-use uzers::{get_group_by_gid, get_user_by_uid};
+use chrono::Local;
+use clap::Parser;
+use rust_ls::{
+    BlockSize, Checksum, DirListing, Entry, EntryFilter, EntryFormatter, FileKind, LinkState,
+    ListingError, LongFormatter, Quoting, Snapshot,
+    archive::{Archive, ArchiveFormat},
+    color::{ColorWhen, LsColors},
+    grid::{self, Cell, print_line},
+    hardlink::HardLinkGroups,
+    json::JsonFormatter,
+    snapshot::ChangeKind,
+    sort::sort_entries,
+    usage::{DirectoryTotals, DiskUsage},
+    watch::DirWatcher,
+};
 
-fn main() -> anyhow::Result<()> {
-    // parse command line argument
-    let path = env::args().nth(1).unwrap_or_else(|| ".".to_string());
+use crate::cli::{Cli, Format};
 
-    // read directory
-    let mut entries: Vec<_> = fs::read_dir(path)?.filter_map(|res| res.ok()).collect();
+/// How long `--watch` waits for a burst of changes to end before listing again.
+const WATCH_SETTLE: Duration = Duration::from_millis(100);
+/// Styles of new, changed and removed rows in `--watch` and `--diff`: bold green, bold yellow
+/// and red.
+const ADDED_STYLE: &str = "1;32";
+const CHANGED_STYLE: &str = "1;33";
+const REMOVED_STYLE: &str = "31";
 
-    // sort by file name
-    entries.sort_by_key(|entry| entry.file_name());
+fn main() {
+    // die quietly when the reader of a pipe goes away (`rust-ls | head`), like other command
+    // line tools, instead of panicking in println!
+    // SAFETY: nothing else is running yet that could be handling signals
+    unsafe {
+        libc::signal(libc::SIGPIPE, libc::SIG_DFL);
+    }
 
-    // print entries
-    for entry in entries {
-        print_direntry(entry)?;
+    // parse command line arguments
+    let cli = Cli::parse();
+    let operands = if cli.paths.is_empty() { vec![PathBuf::from(".")] } else { cli.paths.clone() };
+    let show_headers = cli.is_recursive() || operands.len() > 1;
+
+    // split operands into plain files, which are listed together first, and directories
+    let mut lister = Lister::new(&cli);
+    // the operands of --diff may be snapshots, which are read rather than listed
+    if cli.diff {
+        let [old, new] = cli.paths.as_slice() else {
+            eprintln!("rust-ls: --diff needs two snapshots or directories");
+            process::exit(2);
+        };
+        lister.diff(old, new);
+        process::exit(lister.status);
     }
-    Ok(())
+    let mut files = Vec::new();
+    let mut dirs = Vec::new();
+    let mut archives = Vec::new();
+    for path in operands {
+        if let Some((archive, inner)) = split_archive_path(&path, cli.archive) {
+            archives.push((path, archive, inner));
+            continue;
+        }
+        match lister.stat_operand(&path) {
+            // -d lists directories themselves, except in a tree, where it leaves out files
+            Ok(metadata)
+                if metadata.is_dir() && (!cli.directory || cli.format() == Format::Tree) =>
+            {
+                dirs.push(path)
+            },
+            Ok(_) => {
+                let entry = if cli.follows_operand_links() {
+                    Entry::followed(path.clone(), path.clone())
+                } else {
+                    Entry::new(path.clone(), path.clone())
+                };
+                match entry {
+                    Ok(mut entry) => {
+                        if cli.needs_xattrs() {
+                            entry.read_xattrs();
+                        }
+                        entry.read_contents(cli.content_options());
+                        files.push(entry)
+                    },
+                    // e.g. removed since it was looked at
+                    Err(err) => lister.report("cannot access", &path, &err, true),
+                }
+            },
+            Err(err) => lister.report("cannot access", &path, &err, true),
+        }
+    }
+
+    // the modes below take a single directory, which a failed operand cannot have been
+    let only_dir = files.is_empty() && archives.is_empty() && lister.status == 0;
+    if cli.browse {
+        let ([dir], true) = (dirs.as_slice(), only_dir) else {
+            eprintln!("rust-ls: --browse needs exactly one directory");
+            process::exit(2);
+        };
+        if !io::stdout().is_terminal() {
+            eprintln!("rust-ls: --browse needs a terminal");
+            process::exit(2);
+        }
+        if let Err(err) = browse::run(&cli, dir) {
+            lister.report("cannot browse", dir, &err, true);
+        }
+    } else if let Some(out) = &cli.snapshot {
+        let ([dir], true) = (dirs.as_slice(), only_dir) else {
+            eprintln!("rust-ls: --snapshot needs exactly one directory");
+            process::exit(2);
+        };
+        lister.snapshot(dir, out);
+    } else if cli.watch {
+        let ([dir], true) = (dirs.as_slice(), only_dir) else {
+            eprintln!("rust-ls: --watch needs exactly one directory");
+            process::exit(2);
+        };
+        if let Err(err) = lister.watch(dir) {
+            lister.report("cannot watch", dir, &err, true);
+        }
+    } else {
+        lister.list_files(files);
+        for dir in dirs {
+            lister.list_directory(&dir, show_headers, 0);
+        }
+        for (path, archive, inner) in archives {
+            lister.list_archive(&path, &archive, &inner, show_headers);
+        }
+        lister.finish();
+    }
+    process::exit(lister.status);
+}
+
+/// Prints operands and directory contents, keeping the state shared across all of them.
+struct Lister<'a> {
+    cli: &'a Cli,
+    /// `(dev, ino)` of every directory listed so far in recursive mode
+    visited: HashSet<(u64, u64)>,
+    /// whether anything was printed yet, so sections can be separated by a blank line
+    printed_any: bool,
+    /// which entries of a directory are left out, shared by every listing
+    filter: Arc<EntryFilter>,
+    /// color database, if the output is colorized at all
+    colors: Option<LsColors>,
+    long_formatter: LongFormatter,
+    /// unit of the `total` line and the `-s` column
+    blocks_unit: BlockSize,
+    /// number of records printed so far with `--format=json`
+    json_records: usize,
+    /// directories and other files shown so far with `--tree`, for the closing summary
+    tree_directories: usize,
+    tree_files: usize,
+    /// group numbers of files with several hard links, shared by every listing
+    hardlinks: HardLinkGroups,
+    /// size of everything listed so far, with `--du`
+    usage: Option<DiskUsage>,
+    /// recursive sizes of the directories below the operand being listed, with `--du`
+    totals: Option<Arc<DirectoryTotals>>,
+    /// file system of the operand being listed, with `--one-file-system`
+    root_dev: Option<u64>,
+    /// exit status like GNU `ls`: 0 when all went well, 1 for minor problems (e.g. a
+    /// subdirectory that cannot be opened) and 2 for serious trouble (e.g. a missing operand)
+    status: i32,
 }
 
-fn print_direntry(entry: fs::DirEntry) -> std::io::Result<()> {
-    let metadata = entry.metadata()?;
+impl<'a> Lister<'a> {
+    fn new(cli: &'a Cli) -> Self {
+        let colors = cli
+            .color
+            .unwrap_or(ColorWhen::Never)
+            .enabled()
+            .then(LsColors::from_env);
+        Lister {
+            cli,
+            visited: HashSet::new(),
+            printed_any: false,
+            filter: Arc::new(cli.entry_filter()),
+            colors,
+            long_formatter: cli.long_formatter(),
+            blocks_unit: cli.blocks_unit(),
+            json_records: 0,
+            tree_directories: 0,
+            tree_files: 0,
+            hardlinks: HardLinkGroups::default(),
+            usage: cli.du.then(DiskUsage::default),
+            totals: None,
+            root_dev: None,
+            status: 0,
+        }
+    }
+
+    /// Command line symlinks are followed with `-H` or `-L`, and otherwise those to directories
+    /// are followed unless the link itself is asked for (`-l` or `-d`), like GNU `ls`.
+    fn stat_operand(&self, path: &Path) -> io::Result<fs::Metadata> {
+        if self.cli.follows_operand_links() {
+            // dangling links are reported once their entry is listed
+            fs::metadata(path).or_else(|_| fs::symlink_metadata(path))
+        } else if self.cli.format() == Format::Long || self.cli.directory {
+            fs::symlink_metadata(path)
+        } else {
+            fs::metadata(path).or_else(|_| fs::symlink_metadata(path))
+        }
+    }
+
+    fn list_files(&mut self, mut entries: Vec<Entry>) {
+        if entries.is_empty() {
+            return;
+        }
+        if self.cli.du {
+            for entry in &mut entries {
+                if entry.is_dir() {
+                    // with -d, directories are shown with what they hold, like the others
+                    if let Some(totals) = self.measure(&entry.path) {
+                        totals.apply(entry);
+                    }
+                } else if let Some(usage) = &mut self.usage {
+                    usage.add(entry);
+                }
+            }
+        }
+        sort_entries(&mut entries, &self.cli.sort_options());
+        if self.cli.follows_operand_links() {
+            self.report_dangling(&entries, true);
+        }
+        self.print_entries(&entries);
+        self.printed_any = true;
+    }
+
+    /// Adds up the sizes below the operand `path` for `--du`, and adds them to the run's usage.
+    fn measure(&mut self, path: &Path) -> Option<Arc<DirectoryTotals>> {
+        let (usage, totals) = match DirectoryTotals::walk(path, self.cli.one_file_system) {
+            Ok(walk) => walk,
+            Err(err) => {
+                self.report("cannot access", path, &err, true);
+                return None;
+            },
+        };
+        for (path, err) in &totals.errors {
+            self.report("cannot read", path, err, false);
+        }
+        if let Some(run_usage) = &mut self.usage {
+            run_usage.merge(usage);
+        }
+        Some(Arc::new(totals))
+    }
+
+    /// Whether `entry` is a directory to descend into: not `.` or `..`, and with
+    /// `--one-file-system` on the same file system as the operand.
+    fn descends_into(&self, entry: &Entry) -> bool {
+        entry.is_dir() && !entry.is_implied() && self.root_dev.is_none_or(|dev| entry.dev == dev)
+    }
 
-    // file type and permissions
-    let file_type = entry.file_type()?;
-    let file_type_char = to_file_type_char(&file_type);
-    let mode = to_permission_expression(metadata.permissions().mode());
+    /// Tells about a problem with `path` on stderr and carries on. Problems with the operands
+    /// themselves are serious trouble (exit status 2), those found below them minor (1).
+    fn report(&mut self, what: &str, path: &Path, err: &io::Error, operand: bool) {
+        eprintln!("rust-ls: {} '{}': {}", what, path.display(), describe(err));
+        self.status = self.status.max(if operand { 2 } else { 1 });
+    }
 
-    // hard link count
-    let nlink = metadata.nlink();
+    /// Tells about everything that was left out of the listing of `dir`.
+    fn report_listing_errors(&mut self, dir: &Path, errors: &[ListingError], operand: bool) {
+        for error in errors {
+            match error {
+                ListingError::Entry(path, err) => self.report("cannot access", path, err, false),
+                ListingError::ReadDir(err) => self.report("reading directory", dir, err, operand),
+            }
+        }
+    }
 
-    // owner and group
-    let uid = metadata.uid();
-    let gid = metadata.gid();
+    /// Lists the contents of `path`, and with `-R` every subdirectory after it in the same order.
+    ///
+    /// Symbolic links are never descended into, and each directory is keyed by its `(dev, ino)`
+    /// pair so that a directory reachable twice (e.g. through a bind mount) is only listed once.
+    /// This keeps the walk finite even when the tree contains cycles.
+    ///
+    /// `depth` counts the levels below the operand, so that `--max-depth` can stop the descent.
+    /// Problems are reported on the way, as serious trouble if `path` is the operand itself.
+    fn list_directory(&mut self, path: &Path, show_header: bool, depth: usize) {
+        let operand = depth == 0;
+        if operand {
+            if self.cli.du {
+                self.totals = self.measure(path);
+            }
+            if self.cli.one_file_system {
+                self.root_dev = fs::metadata(path).ok().map(|metadata| metadata.dev());
+            }
+        }
+        if self.cli.format() == Format::Tree {
+            print_line(&self.long_formatter.quoting.quote(path.as_os_str()));
+            match fs::metadata(path) {
+                Ok(root) => {
+                    self.visited.insert((root.dev(), root.ino()));
+                    if self.tree_level() != Some(0) {
+                        self.list_tree(path, "", 1);
+                    }
+                },
+                Err(err) => self.report("cannot access", path, &err, operand),
+            }
+            return;
+        }
+        if self.cli.is_recursive() {
+            let metadata = match fs::metadata(path) {
+                Ok(metadata) => metadata,
+                Err(err) => return self.report("cannot access", path, &err, operand),
+            };
+            if !self.visited.insert((metadata.dev(), metadata.ino())) {
+                eprintln!("rust-ls: {}: not listing already-listed directory", path.display());
+                self.status = self.status.max(1);
+                return;
+            }
+        }
 
-    let user_name = get_user_by_uid(uid)
-        .map(|u| u.name().to_string_lossy().into_owned())
-        .unwrap_or(uid.to_string());
+        let (entries, errors) = match self.dir_listing(path).read_with_errors() {
+            Ok(listing) => listing,
+            Err(err) => return self.report("cannot open directory", path, &err, operand),
+        };
+        self.report_listing_errors(path, &errors, operand);
+        if self.cli.dereference {
+            self.report_dangling(&entries, false);
+        }
 
-    let group_name = get_group_by_gid(gid)
-        .map(|g| g.name().to_string_lossy().into_owned())
-        .unwrap_or(gid.to_string());
+        self.print_header(path, show_header, Some(&entries));
+        self.print_entries(&entries);
+        self.printed_any = true;
 
-    // file size and modification time
-    let file_size = to_human_readable_size(metadata.size());
-    let modified = to_readable_datetime(metadata.modified()?);
+        if self.cli.is_recursive() && self.cli.max_depth.is_none_or(|max| depth < max) {
+            for entry in &entries {
+                if self.descends_into(entry) {
+                    self.list_directory(&entry.path, true, depth + 1);
+                }
+            }
+        }
+    }
 
-    // file name (with symlink target if applicable)
-    let mut file_name = entry.file_name().to_string_lossy().into_owned();
-    if file_type.is_symlink() {
-        if let Ok(target) = fs::read_link(entry.path()) {
-            file_name = format!("{} -> {}", file_name, target.to_string_lossy())
+    /// What comes before the entries of a directory: a blank line after earlier output, the
+    /// `path:` header and, unless `entries` is `None`, the `total` line.
+    fn print_header(&self, path: &Path, show_header: bool, entries: Option<&[Entry]>) {
+        // records carry their own path, so JSON output has no headers
+        if self.cli.is_json() {
+            return;
+        }
+        if self.printed_any {
+            println!();
+        }
+        if show_header {
+            let mut header = self.long_formatter.quoting.quote(path.as_os_str());
+            header.push(b':');
+            print_line(&header);
+        }
+        if let Some(entries) = entries
+            && (self.cli.format() == Format::Long || self.cli.size)
+        {
+            // allocated space of this directory (st_blocks is in 512-byte units); set
+            // against the sizes, a small total gives sparse files away
+            let total_blocks: u64 = entries.iter().map(|entry| entry.blocks).sum();
+            println!("total {}", self.blocks_unit.scale(total_blocks * 512));
         }
     }
 
-    // Print formatted output
-    println!(
-        "{}{} {:>4} {:<8} {:<8} {:>4} {} {}",
-        file_type_char, mode, nlink, user_name, group_name, file_size, modified, file_name
-    );
+    /// How the contents of `path` are read, as asked on the command line.
+    fn dir_listing(&self, path: &Path) -> DirListing {
+        DirListing::new(path)
+            .all(self.cli.all)
+            .almost_all(self.cli.almost_all)
+            .sort(self.cli.sort_options())
+            .filter(self.filter.clone())
+            .xattrs(self.cli.needs_xattrs())
+            .contents(self.cli.content_options())
+            .totals(self.totals.clone())
+            .dereference(self.cli.dereference)
+    }
+
+    /// Lists `path` again every time its entries change (`--watch`), marking rows that are new
+    /// (`+`) or changed (`~`) since the previous listing and naming the entries that went away.
+    /// On a terminal, each listing replaces the previous one. Runs until interrupted or until
+    /// the directory itself is removed.
+    fn watch(&mut self, path: &Path) -> io::Result<()> {
+        let watcher = DirWatcher::new(path)?;
+        let terminal = io::stdout().is_terminal();
+        let mut previous: Option<HashMap<OsString, EntryVersion>> = None;
+        loop {
+            // entries that vanish while being read are simply gone from this listing
+            let entries = match self.dir_listing(path).read() {
+                Ok(entries) => entries,
+                // removed between the event and the listing
+                Err(err) if err.kind() == io::ErrorKind::NotFound => break,
+                Err(err) => return Err(err),
+            };
+            let versions: HashMap<_, _> = entries
+                .iter()
+                .map(|entry| (entry.name.clone(), EntryVersion::of(entry)))
+                .collect();
+
+            if terminal {
+                // move home and clear the screen
+                print!("\x1b[H\x1b[2J");
+            } else if self.printed_any {
+                println!();
+            }
+            let mut header = self.long_formatter.quoting.quote(path.as_os_str());
+            let count = match entries.len() {
+                1 => "1 entry".to_string(),
+                count => format!("{} entries", count),
+            };
+            let updated = format!(": {} (updated {})", count, Local::now().format("%H:%M:%S"));
+            header.extend_from_slice(updated.as_bytes());
+            print_line(&header);
+            let prefixes = self.name_prefixes(&entries);
+            let colors = self.colors.as_ref();
+            for (entry, prefix) in entries.iter().zip(&prefixes) {
+                let marker = match previous.as_ref().map(|previous| previous.get(&entry.name)) {
+                    Some(None) => highlight(b"+", ADDED_STYLE, terminal),
+                    Some(Some(version)) if *version != versions[&entry.name] => {
+                        highlight(b"~", CHANGED_STYLE, terminal)
+                    },
+                    _ => b" ".to_vec(),
+                };
+                let line = if self.cli.format() == Format::Long {
+                    to_long_line(entry, &self.long_formatter, colors)
+                } else {
+                    to_link_name(entry, &self.long_formatter.quoting, colors)
+                };
+                print_line(&[&marker[..], b" ", prefix.as_bytes(), &line].concat());
+            }
+            if let Some(previous) = &previous {
+                let mut removed: Vec<_> = previous
+                    .keys()
+                    .filter(|name| !versions.contains_key(*name))
+                    .collect();
+                removed.sort();
+                for name in removed {
+                    let line = [b"- ", &self.long_formatter.quoting.quote(name)[..]].concat();
+                    print_line(&highlight(&line, REMOVED_STYLE, terminal));
+                }
+            }
+            self.printed_any = true;
+            previous = Some(versions);
+
+            if !watcher.wait(WATCH_SETTLE)? {
+                break;
+            }
+        }
+        eprintln!("rust-ls: {}: directory was removed", path.display());
+        self.status = self.status.max(1);
+        Ok(())
+    }
+
+    /// Lists the directory `inner` of an archive like a directory on disk, and with `-R` the
+    /// directories below it. A member that is not a directory is listed on its own, like a file
+    /// operand.
+    ///
+    /// Members take up no space on disk and belong to no git work tree, so there is no `total`
+    /// line and no git column.
+    fn list_archive(&mut self, operand: &Path, archive: &Path, inner: &Path, show_header: bool) {
+        let contents = match Archive::open(archive) {
+            Ok(contents) => contents,
+            Err(err) => return self.report("cannot read archive", archive, &err, true),
+        };
+        let git = self.long_formatter.git.take();
+        self.list_archive_member(&contents, operand, inner, show_header);
+        self.long_formatter.git = git;
+    }
+
+    fn list_archive_member(
+        &mut self,
+        contents: &Archive,
+        operand: &Path,
+        inner: &Path,
+        show_header: bool,
+    ) {
+        if contents.is_dir(inner) && self.cli.format() == Format::Tree {
+            print_line(&self.long_formatter.quoting.quote(operand.as_os_str()));
+            if self.tree_level() != Some(0) {
+                self.list_archive_tree(contents, inner, "", 1);
+            }
+            return;
+        }
+        if contents.is_dir(inner) {
+            self.list_archive_dir(contents, operand, inner, show_header, 0);
+            return;
+        }
+        match contents.member(inner) {
+            Some(member) => {
+                let mut entry = member.clone();
+                entry.name = operand.into();
+                self.list_files(vec![entry]);
+            },
+            None => {
+                let err = io::Error::from_raw_os_error(libc::ENOENT);
+                self.report("cannot access", operand, &err, true);
+            },
+        }
+    }
+
+    fn list_archive_dir(
+        &mut self,
+        archive: &Archive,
+        path: &Path,
+        inner: &Path,
+        show_header: bool,
+        depth: usize,
+    ) {
+        let entries = self.archive_entries(archive, inner);
+        self.print_header(path, show_header, None);
+        self.print_entries(&entries);
+        self.printed_any = true;
+
+        if self.cli.is_recursive() && self.cli.max_depth.is_none_or(|max| depth < max) {
+            for entry in entries.iter().filter(|entry| entry.is_dir()) {
+                let inner = inner.join(&entry.name);
+                self.list_archive_dir(archive, &entry.path, &inner, true, depth + 1);
+            }
+        }
+    }
+
+    /// Draws the members below the directory `inner` of an archive like [`Lister::list_tree`]
+    /// draws a directory on disk. Archives hold no cycles, so nothing needs to be remembered.
+    fn list_archive_tree(&mut self, archive: &Archive, inner: &Path, prefix: &str, depth: usize) {
+        let mut entries = self.archive_entries(archive, inner);
+        if self.cli.directory {
+            entries.retain(Entry::is_dir);
+        }
+
+        let prefixes = self.name_prefixes(&entries);
+        for (index, (entry, name_prefix)) in entries.iter().zip(prefixes).enumerate() {
+            let last = index + 1 == entries.len();
+            let connector = if last { "└── " } else { "├── " };
+            let name = to_link_name(entry, &self.long_formatter.quoting, self.colors.as_ref());
+            let branch = format!("{}{}{}", prefix, connector, name_prefix);
+            print_line(&[branch.into_bytes(), name].concat());
+            if !entry.is_dir() {
+                self.tree_files += 1;
+                continue;
+            }
 
-    Ok(())
+            self.tree_directories += 1;
+            if self.tree_level().is_none_or(|level| depth < level) {
+                let child_prefix = format!("{}{}", prefix, if last { "    " } else { "│   " });
+                let inner = inner.join(&entry.name);
+                self.list_archive_tree(archive, &inner, &child_prefix, depth + 1);
+            }
+        }
+    }
+
+    /// The members of the directory `inner` of an archive that are to be listed, sorted. They are
+    /// left out like the entries of a directory on disk.
+    fn archive_entries(&self, archive: &Archive, inner: &Path) -> Vec<Entry> {
+        let mut entries = archive.read_dir(inner).unwrap_or_default();
+        let show_hidden = self.cli.all || self.cli.almost_all;
+        entries.retain(|entry| show_hidden || !entry.name.as_encoded_bytes().starts_with(b"."));
+        self.filter
+            .retain(&archive.path().join(inner), &mut entries, show_hidden);
+        sort_entries(&mut entries, &self.cli.sort_options());
+        entries
+    }
+
+    /// Saves everything below `dir` to `out` (`--snapshot`).
+    fn snapshot(&mut self, dir: &Path, out: &Path) {
+        let Some(snapshot) = self.take_snapshot(dir, self.cli.checksum) else {
+            return;
+        };
+        let saved = if out == Path::new("-") {
+            snapshot.save(io::stdout().lock())
+        } else {
+            File::create(out).and_then(|file| snapshot.save(file))
+        };
+        if let Err(err) = saved {
+            self.report("cannot write snapshot", out, &err, true);
+        }
+    }
+
+    /// A snapshot of `dir`, telling about whatever could not be read below it.
+    fn take_snapshot(&mut self, dir: &Path, checksum: Option<Checksum>) -> Option<Snapshot> {
+        match Snapshot::take(dir, self.filter.clone(), checksum) {
+            Ok((snapshot, errors)) => {
+                for (path, err) in &errors {
+                    self.report("cannot access", path, err, false);
+                }
+                Some(snapshot)
+            },
+            Err(err) => {
+                self.report("cannot open directory", dir, &err, true);
+                None
+            },
+        }
+    }
+
+    /// Prints what was added, removed and changed from `old` to `new` (`--diff`), each a saved
+    /// snapshot or a directory read now. Exits like diff(1): 0 when nothing differs, 1 when
+    /// something does, and 2 when something could not be read.
+    fn diff(&mut self, old: &Path, new: &Path) {
+        // snapshots are read first, so that a directory is hashed like the snapshot it is
+        // compared with
+        let mut snapshots = [None, None];
+        for (snapshot, path) in snapshots.iter_mut().zip([old, new]) {
+            if !path.is_dir() {
+                match Snapshot::load(path) {
+                    Ok(loaded) => *snapshot = Some(loaded),
+                    Err(err) => self.report("cannot read snapshot", path, &err, true),
+                }
+            }
+        }
+        let checksum = snapshots
+            .iter()
+            .flatten()
+            .find_map(|snapshot| snapshot.checksum)
+            .or(self.cli.checksum);
+        for (snapshot, path) in snapshots.iter_mut().zip([old, new]) {
+            if path.is_dir() {
+                *snapshot = self.take_snapshot(path, checksum);
+            }
+        }
+        let [Some(old), Some(new)] = snapshots else {
+            self.status = 2;
+            return;
+        };
+
+        let changes = old.diff(&new);
+        match self.cli.format() {
+            Format::Json => println!("{}", serde_json::to_string(&changes).unwrap_or_default()),
+            Format::Ndjson => {
+                for change in &changes {
+                    println!("{}", serde_json::to_string(change).unwrap_or_default());
+                }
+            },
+            _ => {
+                let colored = self.colors.is_some();
+                let quoting = &self.long_formatter.quoting;
+                let mut counts = [0; 3];
+                for change in &changes {
+                    let (marker, style, count) = match change.kind {
+                        ChangeKind::Added => ("+", ADDED_STYLE, &mut counts[0]),
+                        ChangeKind::Removed => ("-", REMOVED_STYLE, &mut counts[1]),
+                        ChangeKind::Changed => ("~", CHANGED_STYLE, &mut counts[2]),
+                    };
+                    *count += 1;
+                    let mut line = format!("{} ", marker).into_bytes();
+                    line.extend(quoting.quote(OsStr::from_bytes(&change.path_bytes)));
+                    for (index, difference) in change.differences.iter().enumerate() {
+                        line.extend_from_slice(if index == 0 { b": " } else { b", " });
+                        line.extend_from_slice(
+                            format!(
+                                "{} {} -> {}",
+                                difference.field, difference.old, difference.new
+                            )
+                            .as_bytes(),
+                        );
+                    }
+                    print_line(&highlight(&line, style, colored));
+                }
+                if !changes.is_empty() {
+                    let [added, removed, changed] = counts;
+                    println!("\n{} added, {} removed, {} changed", added, removed, changed);
+                }
+            },
+        }
+        // trouble reading either side wins over a difference
+        self.status = if self.status != 0 {
+            2
+        } else if changes.is_empty() {
+            0
+        } else {
+            1
+        };
+    }
+
+    /// How many levels below the root a tree goes: `--level`, or `--max-depth`.
+    fn tree_level(&self) -> Option<usize> {
+        self.cli.level.map(NonZeroUsize::get).or(self.cli.max_depth)
+    }
+
+    /// Prints the entries of `path` below the branches in `prefix`, then descends into each
+    /// subdirectory up to `--level`. Like with `-R`, symbolic links are not followed and a
+    /// directory that was already shown is not descended into again.
+    fn list_tree(&mut self, path: &Path, prefix: &str, depth: usize) {
+        // a tree has no `.` and `..` branches, even with -a
+        let (mut entries, errors) = match self
+            .dir_listing(path)
+            .all(false)
+            .almost_all(self.cli.all || self.cli.almost_all)
+            .read_with_errors()
+        {
+            Ok(listing) => listing,
+            // only the root is an operand
+            Err(err) => return self.report("cannot open directory", path, &err, depth == 1),
+        };
+        self.report_listing_errors(path, &errors, depth == 1);
+        if self.cli.dereference {
+            self.report_dangling(&entries, false);
+        }
+        if self.cli.directory {
+            entries.retain(Entry::is_dir);
+        }
+
+        let prefixes = self.name_prefixes(&entries);
+        let names: Vec<_> = entries
+            .iter()
+            .zip(prefixes)
+            .map(|(entry, name_prefix)| {
+                let name = to_link_name(entry, &self.long_formatter.quoting, self.colors.as_ref());
+                [name_prefix.into_bytes(), name].concat()
+            })
+            .collect();
+        for (index, (entry, name)) in entries.iter().zip(names).enumerate() {
+            let last = index + 1 == entries.len();
+            let connector = if last { "└── " } else { "├── " };
+            let line = [prefix.as_bytes(), connector.as_bytes(), &name].concat();
+            if !entry.is_dir() {
+                print_line(&line);
+                self.tree_files += 1;
+                continue;
+            }
+
+            self.tree_directories += 1;
+            if !self.descends_into(entry) {
+                print_line(&line);
+                continue;
+            }
+            if !self.visited.insert(entry.file_id()) {
+                print_line(&[&line[..], b"  [recursive, not followed]"].concat());
+                continue;
+            }
+            print_line(&line);
+            if self.tree_level().is_none_or(|level| depth < level) {
+                let child_prefix = format!("{}{}", prefix, if last { "    " } else { "│   " });
+                self.list_tree(&entry.path, &child_prefix, depth + 1);
+            }
+        }
+    }
+
+    /// Complains about symbolic links that were to be followed but lead nowhere. They are still
+    /// listed, with the metadata of the link itself.
+    fn report_dangling(&mut self, entries: &[Entry], operand: bool) {
+        for entry in entries.iter().filter(|entry| entry.is_dangling()) {
+            let errno = match entry.link_state {
+                Some(LinkState::Loop) => libc::ELOOP,
+                _ => libc::ENOENT,
+            };
+            let err = io::Error::from_raw_os_error(errno);
+            self.report("cannot access", &entry.path, &err, operand);
+        }
+    }
+
+    fn print_entries(&mut self, entries: &[Entry]) {
+        let prefixes = self.name_prefixes(entries);
+        let colors = self.colors.as_ref();
+        let quoting = &self.long_formatter.quoting;
+        match self.cli.format() {
+            Format::Long => {
+                for (entry, prefix) in entries.iter().zip(&prefixes) {
+                    print!("{}", prefix);
+                    print_direntry(entry, &self.long_formatter, colors);
+                    if self.cli.xattrs {
+                        print_xattrs(entry);
+                    }
+                }
+            },
+            Format::SingleColumn => {
+                for (entry, prefix) in entries.iter().zip(&prefixes) {
+                    let name = to_name_cell(entry, quoting, colors).text;
+                    print_line(&[prefix.as_bytes(), &name].concat());
+                }
+            },
+            format @ (Format::Vertical | Format::Across) => {
+                let cells: Vec<_> = entries
+                    .iter()
+                    .zip(&prefixes)
+                    .map(|(entry, prefix)| {
+                        let mut cell = to_name_cell(entry, quoting, colors);
+                        cell.text.splice(0..0, prefix.bytes());
+                        cell.width += prefix.len();
+                        cell
+                    })
+                    .collect();
+                let line_width = self.cli.width.unwrap_or_else(grid::terminal_width);
+                grid::print_grid(&cells, line_width, format == Format::Across);
+            },
+            Format::Json => {
+                // one record per line inside a single array that spans the whole run
+                for entry in entries {
+                    let separator = if self.json_records == 0 { "[" } else { "," };
+                    print_line(
+                        &[separator.as_bytes(), &JsonFormatter.format_entry(entry)].concat(),
+                    );
+                    self.json_records += 1;
+                }
+            },
+            Format::Ndjson => {
+                for entry in entries {
+                    print_line(&JsonFormatter.format_entry(entry));
+                }
+            },
+            Format::Tree => {
+                // file operands are roots without branches
+                for entry in entries {
+                    print_line(&to_link_name(entry, quoting, colors));
+                    self.tree_files += 1;
+                }
+            },
+        }
+    }
+
+    /// What goes before each name: the `-i` and `-s` columns, right-aligned, outside of the long
+    /// format (which has columns of its own) the `-Z`, `--checksum` and `--mime` columns,
+    /// left-aligned, and the `--group-hardlinks` column. Each is followed by a space.
+    fn name_prefixes(&mut self, entries: &[Entry]) -> Vec<String> {
+        let mut prefixes = vec![String::new(); entries.len()];
+        if self.cli.inode {
+            let width = entries
+                .iter()
+                .map(|entry| entry.ino.to_string().len())
+                .max()
+                .unwrap_or(0);
+            for (prefix, entry) in prefixes.iter_mut().zip(entries) {
+                prefix.push_str(&format!("{:>width$} ", entry.ino));
+            }
+        }
+        if self.cli.size {
+            let sizes: Vec<_> = entries
+                .iter()
+                .map(|entry| self.blocks_unit.scale(entry.blocks * 512))
+                .collect();
+            let width = sizes.iter().map(String::len).max().unwrap_or(0);
+            for (prefix, size) in prefixes.iter_mut().zip(sizes) {
+                prefix.push_str(&format!("{:>width$} ", size));
+            }
+        }
+        if self.cli.context && self.cli.format() != Format::Long {
+            let contexts: Vec<_> = entries
+                .iter()
+                .map(|entry| entry.security_context().unwrap_or_else(|| "?".to_string()))
+                .collect();
+            let width = contexts.iter().map(String::len).max().unwrap_or(0);
+            for (prefix, context) in prefixes.iter_mut().zip(contexts) {
+                prefix.push_str(&format!("{:<width$} ", context));
+            }
+        }
+        // the columns read from file contents, which the long format also has of its own
+        let contents = self.cli.content_options();
+        if contents.checksum.is_some() && self.cli.format() != Format::Long {
+            for (prefix, entry) in prefixes.iter_mut().zip(entries) {
+                let checksum = match &entry.checksum {
+                    Some(digest) => digest.hex.as_str(),
+                    None if entry.kind == FileKind::File => "?",
+                    None => "-",
+                };
+                prefix.push_str(&format!("{:<64} ", checksum));
+            }
+        }
+        if contents.mime_type && self.cli.format() != Format::Long {
+            let width = entries
+                .iter()
+                .map(|entry| entry.mime_type.unwrap_or("?").len())
+                .max()
+                .unwrap_or(0);
+            for (prefix, entry) in prefixes.iter_mut().zip(entries) {
+                prefix.push_str(&format!("{:<width$} ", entry.mime_type.unwrap_or("?")));
+            }
+        }
+        if self.cli.group_hardlinks {
+            let groups: Vec<_> = entries
+                .iter()
+                .map(|entry| match self.hardlinks.group(entry) {
+                    Some(group) => format!("#{}", group),
+                    None => String::new(),
+                })
+                .collect();
+            let width = groups.iter().map(String::len).max().unwrap_or(0);
+            if width > 0 {
+                for (prefix, group) in prefixes.iter_mut().zip(groups) {
+                    prefix.push_str(&format!("{:<width$} ", group));
+                }
+            }
+        }
+        prefixes
+    }
+
+    /// Closes the JSON array, or prints the summary of a tree, once everything has been listed.
+    /// The `--du` totals come last, except in JSON, which must stay a single document.
+    fn finish(&self) {
+        match self.cli.format() {
+            Format::Json => println!("{}", if self.json_records == 0 { "[]" } else { "]" }),
+            Format::Tree => {
+                let directories = match self.tree_directories {
+                    1 => "1 directory".to_string(),
+                    count => format!("{} directories", count),
+                };
+                if self.cli.directory {
+                    println!("\n{}", directories);
+                } else {
+                    let files = match self.tree_files {
+                        1 => "1 file".to_string(),
+                        count => format!("{} files", count),
+                    };
+                    println!("\n{}, {}", directories, files);
+                }
+            },
+            _ => {},
+        }
+        if let Some(usage) = &self.usage
+            && !self.cli.is_json()
+        {
+            println!("\n{}", usage.summary(&self.cli.usage_unit()));
+        }
+    }
+}
+
+/// Splits `path` into an archive and the path inside it if it leads into one, as in
+/// `release.zip/bin` or `release.zip/`, which could not name anything else. With `whole`
+/// (`--archive`), an archive named by itself is taken apart too.
+fn split_archive_path(path: &Path, whole: bool) -> Option<(PathBuf, PathBuf)> {
+    if !whole && fs::symlink_metadata(path).is_ok() {
+        return None;
+    }
+    // without the trailing slash, which would keep the archive from being found
+    let path: PathBuf = path.components().collect();
+    for archive in path.ancestors() {
+        if archive.as_os_str().is_empty() {
+            break;
+        }
+        if ArchiveFormat::of(archive).is_some() {
+            let inner = path.strip_prefix(archive).ok()?;
+            return Some((archive.to_path_buf(), inner.to_path_buf()));
+        }
+        // a file or directory that is no archive
+        if fs::symlink_metadata(archive).is_ok() {
+            return None;
+        }
+    }
+    None
+}
+
+/// The message of `err` without the ` (os error N)` that std appends, like GNU tools print it.
+fn describe(err: &io::Error) -> String {
+    let message = err.to_string();
+    match message.rfind(" (os error ") {
+        Some(end) => message[..end].to_string(),
+        None => message,
+    }
 }
 
-fn to_readable_datetime(systime_time: std::time::SystemTime) -> String {
-    let datetime: DateTime<Local> = systime_time.into();
-    datetime.format("%_m월 %d %H:%M").to_string()
+/// What tells one version of an entry from the next in `--watch`: the status change time moves
+/// on every write, rename or metadata change, and the inode changes when a file is replaced.
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct EntryVersion {
+    ino: u64,
+    changed: Option<SystemTime>,
+    size: u64,
 }
 
-fn to_file_type_char(file_type: &fs::FileType) -> char {
-    if file_type.is_dir() {
-        'd'
-    } else if file_type.is_symlink() {
-        'l'
-    } else {
-        '-'
+impl EntryVersion {
+    fn of(entry: &Entry) -> Self {
+        EntryVersion { ino: entry.ino, changed: entry.changed, size: entry.size }
     }
 }
 
-fn to_permission_expression(mode: u32) -> String {
-    let chars = ['r', 'w', 'x'];
-    let mut result = String::with_capacity(9);
-    for i in (0..9).rev() {
-        if (mode >> i) & 1 == 1 {
-            result.push(chars[2 - (i % 3)]);
-        } else {
-            result.push('-');
-        }
+/// `text` in `style` on a terminal, and as it is otherwise.
+fn highlight(text: &[u8], style: &str, terminal: bool) -> Vec<u8> {
+    if !terminal {
+        return text.to_vec();
     }
-    result
+    [format!("\x1b[{}m", style).as_bytes(), text, b"\x1b[0m"].concat()
 }
 
-fn to_human_readable_size(size: u64) -> String {
-    // 1. If size < 1024, return size in bytes (B)
-    if size < 1024 {
-        return format!("{:>4}B", size);
+/// The quoted entry name, colorized when colors are enabled. The width is measured without the
+/// color escapes.
+fn to_name_cell(entry: &Entry, quoting: &Quoting, colors: Option<&LsColors>) -> Cell {
+    let mut cell = Cell::new(quoting.quote(&entry.name));
+    if let Some(colors) = colors {
+        let style = colors.style(entry);
+        cell.text = colors.paint(&cell.text, style);
     }
+    cell
+}
+
+fn print_direntry(entry: &Entry, formatter: &LongFormatter, colors: Option<&LsColors>) {
+    print_line(&to_long_line(entry, formatter, colors));
+}
 
-    // Define units
-    let units = ["B", "K", "M", "G", "T", "P"];
-    let mut size_f = size as f64;
-    let mut unit_index = 0;
+fn to_long_line(entry: &Entry, formatter: &LongFormatter, colors: Option<&LsColors>) -> Vec<u8> {
+    let Some(colors) = colors else {
+        return formatter.format_entry(entry);
+    };
+    let file_name = to_link_name(entry, &formatter.quoting, Some(colors));
+    [format!("{} ", formatter.format_columns(entry)).into_bytes(), file_name].concat()
+}
 
-    // Devide size by 1024 until it's less than 1024
-    while size_f >= 1024.0 && unit_index < units.len() - 1 {
-        size_f /= 1024.0;
-        unit_index += 1;
+/// One line per extended attribute below a long format line, with its size in bytes (`-@`).
+fn print_xattrs(entry: &Entry) {
+    for xattr in &entry.xattrs {
+        println!("\t{}\t{:>6}", xattr.name.to_string_lossy(), xattr.value.len());
     }
+}
 
-    format!("{:>4.1}{}", size_f, units[unit_index])
+/// The name cell's text, followed by ` -> target` for symbolic links.
+fn to_link_name(entry: &Entry, quoting: &Quoting, colors: Option<&LsColors>) -> Vec<u8> {
+    let file_name = to_name_cell(entry, quoting, colors).text;
+    let Some(target) = &entry.link_target else {
+        return file_name;
+    };
+
+    let mut target_name = quoting.quote(target.as_os_str());
+    if let Some(colors) = colors {
+        // the target is colored after what it points to
+        target_name = colors.paint(&target_name, colors.target_style(entry));
+    }
+    [file_name, b" -> ".to_vec(), target_name].concat()
 }
@@ -0,0 +1,37 @@
+use std::{
+    collections::HashMap,
+    sync::{Mutex, OnceLock},
+};
+
+use uzers::{get_group_by_gid, get_user_by_uid};
+
+/// Names looked up so far, including misses, so that each uid and gid goes to the user
+/// database (which may be NSS over the network) only once per run.
+static USER_NAMES: OnceLock<Mutex<HashMap<u32, Option<String>>>> = OnceLock::new();
+static GROUP_NAMES: OnceLock<Mutex<HashMap<u32, Option<String>>>> = OnceLock::new();
+
+/// Name of the user with `uid`, or `None` if there is no such user.
+pub fn user_name(uid: u32) -> Option<String> {
+    cached(&USER_NAMES, uid, |uid| {
+        get_user_by_uid(uid).map(|u| u.name().to_string_lossy().into_owned())
+    })
+}
+
+/// Name of the group with `gid`, or `None` if there is no such group.
+pub fn group_name(gid: u32) -> Option<String> {
+    cached(&GROUP_NAMES, gid, |gid| {
+        get_group_by_gid(gid).map(|g| g.name().to_string_lossy().into_owned())
+    })
+}
+
+fn cached(
+    cache: &OnceLock<Mutex<HashMap<u32, Option<String>>>>,
+    id: u32,
+    lookup: impl FnOnce(u32) -> Option<String>,
+) -> Option<String> {
+    let mut names = cache
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    names.entry(id).or_insert_with(|| lookup(id)).clone()
+}
@@ -0,0 +1,320 @@
+use std::{env, ffi::OsStr, fmt::Write};
+
+/// How names are written so that they can be told apart and reused (`--quoting-style`).
+///
+/// Every style works on the raw bytes of a name and gives bytes back. Bytes that are not valid
+/// UTF-8 are escaped by the styles that have a way to and written as they are by the others, so
+/// two different names never print the same.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum QuotingStyle {
+    /// names as they are (-N)
+    Literal,
+    /// quoted for a POSIX shell when needed
+    Shell,
+    /// always quoted for a POSIX shell
+    ShellAlways,
+    /// like shell, with $'...' escapes for unprintable characters and stray bytes
+    ShellEscape,
+    /// like shell-escape, but always quoted
+    ShellEscapeAlways,
+    /// a C string literal in double quotes (-Q)
+    C,
+    /// like c, without the surrounding quotes (-b)
+    Escape,
+}
+
+impl QuotingStyle {
+    /// The style in `$QUOTING_STYLE`, if it is set and valid.
+    pub fn from_env() -> Option<Self> {
+        QuotingStyle::from_name(&env::var("QUOTING_STYLE").ok()?)
+    }
+
+    /// The style called `name` on the command line, e.g. `shell-escape`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "literal" => Some(QuotingStyle::Literal),
+            "shell" => Some(QuotingStyle::Shell),
+            "shell-always" => Some(QuotingStyle::ShellAlways),
+            "shell-escape" => Some(QuotingStyle::ShellEscape),
+            "shell-escape-always" => Some(QuotingStyle::ShellEscapeAlways),
+            "c" => Some(QuotingStyle::C),
+            "escape" => Some(QuotingStyle::Escape),
+            _ => None,
+        }
+    }
+}
+
+/// A quoting style together with `-q`, which prints unprintable characters as `?` in the styles
+/// that would otherwise print them as they are.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Quoting {
+    pub style: QuotingStyle,
+    pub hide_control_chars: bool,
+}
+
+impl Default for Quoting {
+    fn default() -> Self {
+        Quoting { style: QuotingStyle::Literal, hide_control_chars: false }
+    }
+}
+
+/// A piece of a name: a printable character, an unprintable one, or a byte that is not UTF-8.
+#[derive(Clone, Copy)]
+enum Unit {
+    Printable(char),
+    Control(char),
+    Byte(u8),
+}
+
+impl Quoting {
+    /// The name as it is to be written. It is only UTF-8 when the name is, or when the style
+    /// escapes what is not.
+    pub fn quote(&self, name: &OsStr) -> Vec<u8> {
+        let units = to_units(name.as_encoded_bytes());
+        match self.style {
+            QuotingStyle::Literal => self.literal(&units),
+            QuotingStyle::Shell => self.shell(&units, false),
+            QuotingStyle::ShellAlways => self.shell(&units, true),
+            QuotingStyle::ShellEscape => shell_escape(&units, false),
+            QuotingStyle::ShellEscapeAlways => shell_escape(&units, true),
+            QuotingStyle::C => format!("\"{}\"", c_escape(&units, false)).into_bytes(),
+            QuotingStyle::Escape => c_escape(&units, true).into_bytes(),
+        }
+    }
+
+    fn literal(&self, units: &[Unit]) -> Vec<u8> {
+        let mut result = Vec::with_capacity(units.len());
+        for unit in units {
+            match *unit {
+                Unit::Control(_) | Unit::Byte(_) if self.hide_control_chars => result.push(b'?'),
+                Unit::Printable(c) | Unit::Control(c) => {
+                    result.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes())
+                },
+                Unit::Byte(byte) => result.push(byte),
+            }
+        }
+        result
+    }
+
+    /// Shell quoting without escapes, so unprintable characters can only be kept or hidden.
+    fn shell(&self, units: &[Unit], always: bool) -> Vec<u8> {
+        let text = self.literal(units);
+        if always || needs_shell_quotes(units) { shell_quote(&text) } else { text }
+    }
+}
+
+fn to_units(bytes: &[u8]) -> Vec<Unit> {
+    let mut units = Vec::with_capacity(bytes.len());
+    for chunk in bytes.utf8_chunks() {
+        units.extend(
+            chunk
+                .valid()
+                .chars()
+                .map(|c| if c.is_control() { Unit::Control(c) } else { Unit::Printable(c) }),
+        );
+        units.extend(chunk.invalid().iter().map(|&byte| Unit::Byte(byte)));
+    }
+    units
+}
+
+/// Whether a name would not reach the shell as one unchanged word: empty names, and names with
+/// whitespace, glob or other special characters anywhere.
+fn needs_shell_quotes(units: &[Unit]) -> bool {
+    units.is_empty()
+        || units.iter().any(|unit| match *unit {
+            Unit::Printable(c) => {
+                c.is_ascii() && !c.is_ascii_alphanumeric() && !"%+,-./:=@_^".contains(c)
+            },
+            Unit::Control(_) | Unit::Byte(_) => true,
+        })
+}
+
+/// Wraps text in single quotes, or in double quotes when that avoids escaping a single quote.
+fn shell_quote(text: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(text.len() + 2);
+    if text.contains(&b'\'') && !text.iter().any(|byte| b"\"$`\\!".contains(byte)) {
+        result.push(b'"');
+        result.extend_from_slice(text);
+        result.push(b'"');
+    } else {
+        result.push(b'\'');
+        for &byte in text {
+            match byte {
+                b'\'' => result.extend_from_slice(b"'\\''"),
+                _ => result.push(byte),
+            }
+        }
+        result.push(b'\'');
+    }
+    result
+}
+
+/// Quotes printable runs and writes everything else as `$'\NNN'`, e.g. `'a'$'\n''b'`.
+fn shell_escape(units: &[Unit], always: bool) -> Vec<u8> {
+    let has_escapes = units.iter().any(|unit| !matches!(unit, Unit::Printable(_)));
+    if !has_escapes {
+        let text = to_printable_text(units);
+        return if always || needs_shell_quotes(units) {
+            shell_quote(text.as_bytes())
+        } else {
+            text.into_bytes()
+        };
+    }
+
+    let mut result = Vec::new();
+    let mut rest = units;
+    while !rest.is_empty() {
+        let printable = matches!(rest[0], Unit::Printable(_));
+        let run_len = rest
+            .iter()
+            .position(|unit| matches!(unit, Unit::Printable(_)) != printable)
+            .unwrap_or(rest.len());
+        let (run, tail) = rest.split_at(run_len);
+        if printable {
+            result.extend(shell_quote(to_printable_text(run).as_bytes()));
+        } else {
+            result.extend_from_slice(format!("$'{}'", c_escape(run, false)).as_bytes());
+        }
+        rest = tail;
+    }
+    result
+}
+
+/// The printable characters among `units`.
+fn to_printable_text(units: &[Unit]) -> String {
+    units
+        .iter()
+        .filter_map(|unit| match *unit {
+            Unit::Printable(c) => Some(c),
+            _ => None,
+        })
+        .collect()
+}
+
+/// C escapes for backslashes, double quotes, unprintable characters and stray bytes. The escape
+/// style also escapes spaces, since it has no quotes around the name.
+fn c_escape(units: &[Unit], escape_spaces: bool) -> String {
+    let mut result = String::new();
+    for unit in units {
+        match *unit {
+            Unit::Printable('\\') => result.push_str("\\\\"),
+            Unit::Printable('"') if !escape_spaces => result.push_str("\\\""),
+            Unit::Printable(' ') if escape_spaces => result.push_str("\\ "),
+            Unit::Printable(c) => result.push(c),
+            Unit::Control(c) => match c {
+                '\x07' => result.push_str("\\a"),
+                '\x08' => result.push_str("\\b"),
+                '\x0c' => result.push_str("\\f"),
+                '\n' => result.push_str("\\n"),
+                '\r' => result.push_str("\\r"),
+                '\t' => result.push_str("\\t"),
+                '\x0b' => result.push_str("\\v"),
+                _ => {
+                    let mut buf = [0; 4];
+                    for byte in c.encode_utf8(&mut buf).bytes() {
+                        let _ = write!(result, "\\{:03o}", byte);
+                    }
+                },
+            },
+            Unit::Byte(byte) => {
+                let _ = write!(result, "\\{:03o}", byte);
+            },
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::ffi::OsStrExt;
+
+    use super::*;
+
+    const STYLES: [QuotingStyle; 7] = [
+        QuotingStyle::Literal,
+        QuotingStyle::Shell,
+        QuotingStyle::ShellAlways,
+        QuotingStyle::ShellEscape,
+        QuotingStyle::ShellEscapeAlways,
+        QuotingStyle::C,
+        QuotingStyle::Escape,
+    ];
+
+    fn quote(style: QuotingStyle, name: &[u8]) -> Vec<u8> {
+        Quoting { style, hide_control_chars: false }.quote(OsStr::from_bytes(name))
+    }
+
+    fn quote_hidden(style: QuotingStyle, name: &[u8]) -> Vec<u8> {
+        Quoting { style, hide_control_chars: true }.quote(OsStr::from_bytes(name))
+    }
+
+    #[test]
+    fn plain_names() {
+        assert_eq!(quote(QuotingStyle::Literal, b"a b"), b"a b");
+        assert_eq!(quote(QuotingStyle::Shell, b"abc"), b"abc");
+        assert_eq!(quote(QuotingStyle::Shell, b"a b"), b"'a b'");
+        assert_eq!(quote(QuotingStyle::Shell, b"it's"), b"\"it's\"");
+        assert_eq!(quote(QuotingStyle::Shell, b"it's $x"), b"'it'\\''s $x'");
+        assert_eq!(quote(QuotingStyle::ShellAlways, b"abc"), b"'abc'");
+        assert_eq!(quote(QuotingStyle::ShellEscape, b"abc"), b"abc");
+        assert_eq!(quote(QuotingStyle::ShellEscapeAlways, b"abc"), b"'abc'");
+        assert_eq!(quote(QuotingStyle::C, b"a\"b"), b"\"a\\\"b\"");
+        assert_eq!(quote(QuotingStyle::Escape, b"a b"), b"a\\ b");
+    }
+
+    #[test]
+    fn invalid_bytes() {
+        let name = b"a\xffb";
+        assert_eq!(quote(QuotingStyle::Literal, name), b"a\xffb");
+        assert_eq!(quote(QuotingStyle::Shell, name), b"'a\xffb'");
+        assert_eq!(quote(QuotingStyle::ShellAlways, name), b"'a\xffb'");
+        assert_eq!(quote(QuotingStyle::ShellEscape, name), b"'a'$'\\377''b'");
+        assert_eq!(quote(QuotingStyle::ShellEscapeAlways, name), b"'a'$'\\377''b'");
+        assert_eq!(quote(QuotingStyle::C, name), b"\"a\\377b\"");
+        assert_eq!(quote(QuotingStyle::Escape, name), b"a\\377b");
+    }
+
+    #[test]
+    fn control_characters() {
+        let name = b"a\nb\x01";
+        assert_eq!(quote(QuotingStyle::Literal, name), b"a\nb\x01");
+        assert_eq!(quote(QuotingStyle::Shell, name), b"'a\nb\x01'");
+        assert_eq!(quote(QuotingStyle::ShellAlways, name), b"'a\nb\x01'");
+        assert_eq!(quote(QuotingStyle::ShellEscape, name), b"'a'$'\\n''b'$'\\001'");
+        assert_eq!(quote(QuotingStyle::ShellEscapeAlways, name), b"'a'$'\\n''b'$'\\001'");
+        assert_eq!(quote(QuotingStyle::C, name), b"\"a\\nb\\001\"");
+        assert_eq!(quote(QuotingStyle::Escape, name), b"a\\nb\\001");
+    }
+
+    #[test]
+    fn hidden_control_characters() {
+        let name = b"a\n\xffb";
+        assert_eq!(quote_hidden(QuotingStyle::Literal, name), b"a??b");
+        assert_eq!(quote_hidden(QuotingStyle::Shell, name), b"'a??b'");
+        assert_eq!(quote_hidden(QuotingStyle::ShellAlways, name), b"'a??b'");
+        // the escaping styles have a way to show them, so -q changes nothing
+        assert_eq!(quote_hidden(QuotingStyle::ShellEscape, name), b"'a'$'\\n\\377''b'");
+        assert_eq!(quote_hidden(QuotingStyle::C, name), b"\"a\\n\\377b\"");
+        assert_eq!(quote_hidden(QuotingStyle::Escape, name), b"a\\n\\377b");
+    }
+
+    #[cfg(feature = "cli")]
+    #[test]
+    fn names_match_the_command_line() {
+        use clap::ValueEnum;
+
+        for style in STYLES {
+            let name = style.to_possible_value().unwrap();
+            assert_eq!(QuotingStyle::from_name(name.get_name()), Some(style));
+        }
+    }
+
+    #[test]
+    fn different_names_stay_different() {
+        for style in STYLES {
+            assert_ne!(quote(style, b"a\xff"), quote(style, b"a\xfe"), "{style:?}");
+            assert_ne!(quote(style, b"a\xff"), quote(style, "a\u{fffd}".as_bytes()), "{style:?}");
+        }
+    }
+}
@@ -0,0 +1,283 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{self, BufReader, BufWriter, Write},
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::SystemTime,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    content::{Checksum, ContentOptions},
+    entry::Entry,
+    filter::EntryFilter,
+    format::to_permission_expression,
+    json::to_rfc3339,
+    listing::{DirListing, ListingError},
+    owner::{group_name, user_name},
+};
+
+/// Version of the snapshot format written by this release.
+const VERSION: u32 = 1;
+
+/// A recursive listing of a directory saved to compare it later (`--snapshot`, `--diff`).
+///
+/// Paths are relative to the directory, so that a snapshot of one copy of a tree can be
+/// compared with another copy, e.g. a staging and a production deployment.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub version: u32,
+    /// the directory that was read, as it was given
+    pub root: String,
+    /// when the snapshot was taken, RFC 3339 in UTC
+    pub taken: String,
+    /// what regular files were hashed with, if they were
+    pub checksum: Option<Checksum>,
+    /// every file below the root, dotfiles included, in the order of their paths
+    pub files: Vec<FileRecord>,
+}
+
+/// What a snapshot records about a file.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileRecord {
+    /// relative to the root, with `/` between components; lossy UTF-8, for display
+    pub path: String,
+    /// the path as it is on disk, which files are matched by
+    pub path_bytes: Vec<u8>,
+    #[serde(rename = "type")]
+    pub file_type: String,
+    /// permission bits, including setuid, setgid and sticky
+    pub mode: u32,
+    /// the mode string of the long format, e.g. `drwxr-xr-x`
+    pub permissions: String,
+    pub uid: u32,
+    pub user: Option<String>,
+    pub gid: u32,
+    pub group: Option<String>,
+    pub size: u64,
+    pub modified: Option<String>,
+    pub symlink_target: Option<String>,
+    /// `algorithm:hex`, for regular files if the snapshot was taken with a checksum
+    pub checksum: Option<String>,
+}
+
+impl FileRecord {
+    fn new(entry: &Entry, root: &Path) -> Self {
+        let path = entry.path.strip_prefix(root).unwrap_or(&entry.path);
+        FileRecord {
+            path: path.to_string_lossy().into_owned(),
+            path_bytes: path.as_os_str().as_bytes().to_vec(),
+            file_type: entry.kind.name().to_string(),
+            mode: entry.mode,
+            permissions: format!(
+                "{}{}",
+                entry.kind.to_char(),
+                to_permission_expression(entry.mode)
+            ),
+            uid: entry.uid,
+            user: entry.user.clone().or_else(|| user_name(entry.uid)),
+            gid: entry.gid,
+            group: entry.group.clone().or_else(|| group_name(entry.gid)),
+            size: entry.size,
+            modified: entry.modified.map(to_rfc3339),
+            symlink_target: entry
+                .link_target
+                .as_ref()
+                .map(|target| target.to_string_lossy().into_owned()),
+            checksum: entry.checksum.as_ref().map(ToString::to_string),
+        }
+    }
+
+    /// What changed from `self` to `new`. The size and time of directories are left out, since
+    /// they change with every entry added or removed, and those entries are compared on their
+    /// own. Checksums are only compared when both sides have one, made with the same algorithm.
+    pub fn differences(&self, new: &FileRecord) -> Vec<Difference> {
+        let mut differences = Vec::new();
+        let mut compare = |field, old: String, new: String| {
+            if old != new {
+                differences.push(Difference { field, old, new });
+            }
+        };
+        compare("mode", self.permissions.clone(), new.permissions.clone());
+        if self.uid != new.uid {
+            compare("owner", self.owner(), new.owner());
+        }
+        if self.gid != new.gid {
+            compare("group", self.group(), new.group());
+        }
+        let both_directories = self.file_type == "directory" && new.file_type == "directory";
+        if !both_directories {
+            compare("size", self.size.to_string(), new.size.to_string());
+            compare("modified", to_text(&self.modified), to_text(&new.modified));
+        }
+        compare("target", to_text(&self.symlink_target), to_text(&new.symlink_target));
+        if let (Some(old), Some(new)) = (&self.checksum, &new.checksum)
+            && old.split(':').next() == new.split(':').next()
+        {
+            compare("checksum", old.clone(), new.clone());
+        }
+        differences
+    }
+
+    /// The user name, or the uid if it has none.
+    fn owner(&self) -> String {
+        self.user.clone().unwrap_or_else(|| self.uid.to_string())
+    }
+
+    fn group(&self) -> String {
+        self.group.clone().unwrap_or_else(|| self.gid.to_string())
+    }
+}
+
+fn to_text(value: &Option<String>) -> String {
+    value.clone().unwrap_or_else(|| "-".to_string())
+}
+
+/// A file that differs between two snapshots.
+#[derive(Debug, Serialize)]
+pub struct Change {
+    pub path: String,
+    pub path_bytes: Vec<u8>,
+    pub kind: ChangeKind,
+    /// what differs, for changed files
+    pub differences: Vec<Difference>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+/// One field of a changed file, with its old and new value as they are shown.
+#[derive(Debug, Serialize)]
+pub struct Difference {
+    /// `mode`, `owner`, `group`, `size`, `modified`, `target` or `checksum`
+    pub field: &'static str,
+    pub old: String,
+    pub new: String,
+}
+
+impl Snapshot {
+    /// Reads every file below `root`, dotfiles included, without following symbolic links.
+    /// Files left out by `filter` are not recorded, and with `checksum` regular files are hashed.
+    /// Only a root that cannot be read is an error; what could not be read below it is returned
+    /// next to the snapshot, in the order of the paths.
+    pub fn take(
+        root: &Path,
+        filter: Arc<EntryFilter>,
+        checksum: Option<Checksum>,
+    ) -> io::Result<(Self, Vec<(PathBuf, io::Error)>)> {
+        let contents = ContentOptions { mime_type: false, checksum };
+        let mut files = Vec::new();
+        let mut errors = Vec::new();
+        let mut dirs = vec![root.to_path_buf()];
+        while let Some(dir) = dirs.pop() {
+            let listing = DirListing::new(&dir)
+                .almost_all(true)
+                .filter(filter.clone())
+                .contents(contents);
+            let (entries, dir_errors) = match listing.read_with_errors() {
+                Ok(read) => read,
+                Err(err) if dir == root => return Err(err),
+                Err(err) => {
+                    errors.push((dir, err));
+                    continue;
+                },
+            };
+            for error in dir_errors {
+                errors.push(match error {
+                    ListingError::Entry(path, err) => (path, err),
+                    ListingError::ReadDir(err) => (dir.clone(), err),
+                });
+            }
+            for entry in entries {
+                if entry.is_dir() {
+                    dirs.push(entry.path.clone());
+                }
+                files.push(FileRecord::new(&entry, root));
+            }
+        }
+        files.sort_by(|a, b| a.path_bytes.cmp(&b.path_bytes));
+        errors.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        let snapshot = Snapshot {
+            version: VERSION,
+            root: root.to_string_lossy().into_owned(),
+            taken: to_rfc3339(SystemTime::now()),
+            checksum,
+            files,
+        };
+        Ok((snapshot, errors))
+    }
+
+    /// Reads a snapshot saved with [`Snapshot::save`].
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let reader = BufReader::new(File::open(path)?);
+        let snapshot: Snapshot = serde_json::from_reader(reader)?;
+        if snapshot.version > VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsupported snapshot version {}", snapshot.version),
+            ));
+        }
+        Ok(snapshot)
+    }
+
+    /// Writes the snapshot as indented JSON, which keeps snapshots readable and easy to keep
+    /// under version control.
+    pub fn save(&self, writer: impl Write) -> io::Result<()> {
+        let mut writer = BufWriter::new(writer);
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        writeln!(writer)?;
+        writer.flush()
+    }
+
+    /// The files that were added, removed or changed from `self` to `new`, in the order of their
+    /// paths. Files are matched by their paths as they are on disk, so that names which are not
+    /// UTF-8 are never taken for one another.
+    pub fn diff(&self, new: &Snapshot) -> Vec<Change> {
+        let old_files: BTreeMap<_, _> = self
+            .files
+            .iter()
+            .map(|file| (file.path_bytes.as_slice(), file))
+            .collect();
+        let new_files: BTreeMap<_, _> = new
+            .files
+            .iter()
+            .map(|file| (file.path_bytes.as_slice(), file))
+            .collect();
+
+        let mut paths: Vec<_> = old_files.keys().chain(new_files.keys()).collect();
+        paths.sort();
+        paths.dedup();
+        paths
+            .into_iter()
+            .filter_map(|&path| {
+                let (kind, differences) = match (old_files.get(path), new_files.get(path)) {
+                    (Some(old), Some(new)) => {
+                        let differences = old.differences(new);
+                        if differences.is_empty() {
+                            return None;
+                        }
+                        (ChangeKind::Changed, differences)
+                    },
+                    (None, _) => (ChangeKind::Added, Vec::new()),
+                    (_, None) => (ChangeKind::Removed, Vec::new()),
+                };
+                let file = old_files.get(path).or(new_files.get(path))?;
+                Some(Change {
+                    path: file.path.clone(),
+                    path_bytes: path.to_vec(),
+                    kind,
+                    differences,
+                })
+            })
+            .collect()
+    }
+}
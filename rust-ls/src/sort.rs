@@ -0,0 +1,262 @@
+use std::cmp::Ordering;
+
+use crate::entry::{Entry, TimeField};
+
+/// Key to order a listing by, as selected by `--sort` or one of its short flags.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum SortKey {
+    /// directory order (-U)
+    None,
+    /// file name, in byte order
+    Name,
+    /// file size, largest first (-S)
+    Size,
+    /// time, newest first (-t); see `--time`
+    Time,
+    /// natural sort of (version) numbers within names (-v)
+    Version,
+    /// file extension, alphabetically (-X)
+    Extension,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct SortOptions {
+    pub key: SortKey,
+    pub reverse: bool,
+    pub directories_first: bool,
+    /// timestamp compared by [`SortKey::Time`]
+    pub time_field: TimeField,
+}
+
+impl Default for SortOptions {
+    fn default() -> Self {
+        SortOptions {
+            key: SortKey::Name,
+            reverse: false,
+            directories_first: false,
+            time_field: TimeField::Modification,
+        }
+    }
+}
+
+/// Sorts `entries` in place.
+///
+/// Every key is computed from the metadata already stored in the entries, so sorting never
+/// touches the file system. The sort is stable, and ties on the primary key fall back to the
+/// name so that the output is deterministic.
+pub fn sort_entries(entries: &mut [Entry], options: &SortOptions) {
+    if options.key == SortKey::None && !options.directories_first {
+        return;
+    }
+    entries.sort_by(|a, b| compare(a, b, options));
+}
+
+fn compare(a: &Entry, b: &Entry, options: &SortOptions) -> Ordering {
+    // --group-directories-first is applied regardless of -r
+    if options.directories_first {
+        let ordering = b.is_dir().cmp(&a.is_dir());
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+
+    let by_name = || a.name.cmp(&b.name);
+    let ordering = match options.key {
+        SortKey::None => return Ordering::Equal,
+        SortKey::Name => by_name(),
+        SortKey::Size => b.size.cmp(&a.size).then_with(by_name),
+        SortKey::Time => {
+            let field = options.time_field;
+            b.time(field).cmp(&a.time(field)).then_with(by_name)
+        },
+        SortKey::Version => {
+            version_cmp(a.name.as_encoded_bytes(), b.name.as_encoded_bytes()).then_with(by_name)
+        },
+        SortKey::Extension => {
+            let a_ext = extension(a.name.as_encoded_bytes());
+            let b_ext = extension(b.name.as_encoded_bytes());
+            a_ext.cmp(b_ext).then_with(by_name)
+        },
+    };
+
+    if options.reverse { ordering.reverse() } else { ordering }
+}
+
+/// Everything from the last `.` on, or nothing when the name has no dot (like GNU `ls -X`).
+fn extension(name: &[u8]) -> &[u8] {
+    match name.iter().rposition(|&c| c == b'.') {
+        Some(index) => &name[index..],
+        None => &[],
+    }
+}
+
+/// Compares two file names the way GNU `filevercmp` does, so that `file-9.tar.gz` sorts before
+/// `file-10.tar.gz`.
+///
+/// `.` and `..` come first, then other hidden files, then the rest. A trailing file suffix such
+/// as `.tar.gz` is ignored in the first pass so that it does not get mixed into the version part.
+pub fn version_cmp(a: &[u8], b: &[u8]) -> Ordering {
+    if a == b {
+        return Ordering::Equal;
+    }
+
+    // special names and hidden files
+    for special in [&b""[..], b".", b".."] {
+        match (a == special, b == special) {
+            (true, _) => return Ordering::Less,
+            (_, true) => return Ordering::Greater,
+            _ => {},
+        }
+    }
+    let a_hidden = a.first() == Some(&b'.');
+    let b_hidden = b.first() == Some(&b'.');
+    if a_hidden != b_hidden {
+        return b_hidden.cmp(&a_hidden);
+    }
+    let (a, b) = if a_hidden { (&a[1..], &b[1..]) } else { (a, b) };
+
+    let a_prefix = &a[..a.len() - file_suffix_len(a)];
+    let b_prefix = &b[..b.len() - file_suffix_len(b)];
+    let ordering =
+        if a_prefix == b_prefix { Ordering::Equal } else { verrevcmp(a_prefix, b_prefix) };
+    ordering
+        .then_with(|| verrevcmp(a, b))
+        .then_with(|| a.cmp(b))
+}
+
+/// Length of the file suffix matching `(\.[A-Za-z~][A-Za-z0-9~]*)*$`.
+fn file_suffix_len(name: &[u8]) -> usize {
+    let mut suffix_start = name.len();
+    let mut i = name.len();
+    while i > 0 {
+        i -= 1;
+        let c = name[i];
+        if c == b'.' {
+            let is_suffix =
+                i + 1 < suffix_start && (name[i + 1].is_ascii_alphabetic() || name[i + 1] == b'~');
+            if !is_suffix {
+                break;
+            }
+            suffix_start = i;
+        } else if !(c.is_ascii_alphanumeric() || c == b'~') {
+            break;
+        }
+    }
+    name.len() - suffix_start
+}
+
+/// Ordering weight of a non-digit byte: `~` sorts before everything, even the end of the string,
+/// and letters sort before other characters.
+fn char_order(c: Option<u8>) -> i32 {
+    match c {
+        None => 0,
+        Some(c) if c.is_ascii_digit() => 0,
+        Some(c) if c.is_ascii_alphabetic() => c as i32,
+        Some(b'~') => -1,
+        Some(c) => c as i32 + 256,
+    }
+}
+
+/// The Debian version comparison: alternately compares runs of non-digits byte by byte and runs
+/// of digits numerically.
+fn verrevcmp(a: &[u8], b: &[u8]) -> Ordering {
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        // non-digit run
+        while (i < a.len() && !a[i].is_ascii_digit()) || (j < b.len() && !b[j].is_ascii_digit()) {
+            let a_order = char_order(a.get(i).copied());
+            let b_order = char_order(b.get(j).copied());
+            if a_order != b_order {
+                return a_order.cmp(&b_order);
+            }
+            i += 1;
+            j += 1;
+        }
+
+        // digit run, ignoring leading zeros
+        while i < a.len() && a[i] == b'0' {
+            i += 1;
+        }
+        while j < b.len() && b[j] == b'0' {
+            j += 1;
+        }
+        let mut first_diff = Ordering::Equal;
+        while i < a.len() && a[i].is_ascii_digit() && j < b.len() && b[j].is_ascii_digit() {
+            if first_diff == Ordering::Equal {
+                first_diff = a[i].cmp(&b[j]);
+            }
+            i += 1;
+            j += 1;
+        }
+        if i < a.len() && a[i].is_ascii_digit() {
+            return Ordering::Greater;
+        }
+        if j < b.len() && b[j].is_ascii_digit() {
+            return Ordering::Less;
+        }
+        if first_diff != Ordering::Equal {
+            return first_diff;
+        }
+    }
+    Ordering::Equal
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Checks that `names` are in strictly increasing order, both ways round.
+    fn assert_ascending(names: &[&str]) {
+        for (index, a) in names.iter().enumerate() {
+            assert_eq!(version_cmp(a.as_bytes(), a.as_bytes()), Ordering::Equal, "{a:?}");
+            for b in &names[index + 1..] {
+                assert_eq!(
+                    version_cmp(a.as_bytes(), b.as_bytes()),
+                    Ordering::Less,
+                    "{a:?} < {b:?}"
+                );
+                assert_eq!(
+                    version_cmp(b.as_bytes(), a.as_bytes()),
+                    Ordering::Greater,
+                    "{b:?} > {a:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn special_and_hidden_names_come_first() {
+        assert_ascending(&["", ".", "..", ".0", ".9", ".A", ".Z", ".a~", ".a", ".b", "0", "a"]);
+    }
+
+    #[test]
+    fn tilde_sorts_before_everything() {
+        assert_ascending(&["a~", "a", "a.b~", "a.b", "b~", "b"]);
+    }
+
+    #[test]
+    fn numbers_compare_by_value() {
+        assert_ascending(&["1.2", "1.9", "1.10", "1.10.1", "9", "10"]);
+        assert_ascending(&["file-9.txt", "file-10.txt", "file-100.txt"]);
+    }
+
+    #[test]
+    fn file_suffix_is_compared_last() {
+        assert_ascending(&["file-9.tar.gz", "file-10.tar.gz", "file-10.tar.gz.~1~"]);
+        assert_ascending(&["gcc-10.fc9.tar.gz", "gcc-10.8.12-0.7rc2.fc9.tar.bz2"]);
+        assert_eq!(file_suffix_len(b"file-10.tar.gz"), b".tar.gz".len());
+        assert_eq!(file_suffix_len(b"file-10"), 0);
+    }
+
+    #[test]
+    fn leading_zeros_only_break_ties() {
+        assert_ascending(&["a01", "a1", "a002", "a2"]);
+    }
+
+    #[test]
+    fn shorter_name_comes_first() {
+        assert_ascending(&["a", "a0", "a0b"]);
+        assert_ascending(&["foo", "foo1", "foo1a"]);
+    }
+}
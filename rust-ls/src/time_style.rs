@@ -0,0 +1,135 @@
+use std::{
+    env,
+    str::FromStr,
+    time::{Duration, SystemTime},
+};
+
+use chrono::{
+    DateTime, Local, Locale,
+    format::{Item, StrftimeItems},
+};
+
+use crate::format::TimeFormatter;
+
+/// Half of an average Gregorian year, the age from which GNU `ls` shows the year instead of the
+/// time of day.
+const SIX_MONTHS: Duration = Duration::from_secs(31_556_952 / 2);
+
+/// How the time column of the long format is written (`--time-style`).
+///
+/// Styles with two formats pick the first one for recent files and the second one for files
+/// that are older than six months or have a timestamp in the future, like GNU `ls`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum TimeStyle {
+    /// `2026-01-30 11:45:02.123456789 +0900`
+    FullIso,
+    /// `2026-01-30 11:45`
+    LongIso,
+    /// `01-30 11:45` for recent files, `2025-01-30` for old ones
+    Iso,
+    /// month and day with the month names of the current locale, with the time or the year
+    #[default]
+    Locale,
+    /// `strftime`-like formats given as `+RECENT` or `+RECENT\nOLD`
+    Custom { recent: String, old: String },
+}
+
+impl FromStr for TimeStyle {
+    type Err = String;
+
+    /// Parses a style name or `+FORMAT`. A `posix-` prefix selects the style only outside of the
+    /// POSIX locale, where the locale style is used instead.
+    fn from_str(style: &str) -> Result<Self, Self::Err> {
+        if let Some(style) = style.strip_prefix("posix-") {
+            return if is_posix_locale() { Ok(TimeStyle::Locale) } else { style.parse() };
+        }
+        match style {
+            "full-iso" => Ok(TimeStyle::FullIso),
+            "long-iso" => Ok(TimeStyle::LongIso),
+            "iso" => Ok(TimeStyle::Iso),
+            "locale" => Ok(TimeStyle::Locale),
+            _ => {
+                let Some(formats) = style.strip_prefix('+') else {
+                    return Err(format!(
+                        "invalid time style '{}' (expected full-iso, long-iso, iso, locale or \
+                         +FORMAT)",
+                        style
+                    ));
+                };
+                let (recent, old) = formats.split_once('\n').unwrap_or((formats, formats));
+                Ok(TimeStyle::Custom {
+                    recent: to_chrono_format(recent)?,
+                    old: to_chrono_format(old)?,
+                })
+            },
+        }
+    }
+}
+
+impl TimeStyle {
+    /// The style in `$TIME_STYLE`, if it is set and valid.
+    pub fn from_env() -> Option<Self> {
+        env::var("TIME_STYLE").ok()?.parse().ok()
+    }
+
+    /// Formats for recent and old timestamps.
+    fn formats(&self) -> (&str, &str) {
+        match self {
+            TimeStyle::FullIso => ("%Y-%m-%d %H:%M:%S.%f %z", "%Y-%m-%d %H:%M:%S.%f %z"),
+            TimeStyle::LongIso => ("%Y-%m-%d %H:%M", "%Y-%m-%d %H:%M"),
+            TimeStyle::Iso => ("%m-%d %H:%M", "%Y-%m-%d "),
+            TimeStyle::Locale => ("%b %e %H:%M", "%b %e  %Y"),
+            TimeStyle::Custom { recent, old } => (recent, old),
+        }
+    }
+}
+
+impl TimeFormatter for TimeStyle {
+    /// Month and weekday names, and the `%c`/`%x`/`%X` formats, come from the locale.
+    fn format_time(&self, time: SystemTime) -> String {
+        let (recent, old) = self.formats();
+        let format = if is_recent(time, SystemTime::now()) { recent } else { old };
+        DateTime::<Local>::from(time)
+            .format_localized(format, chrono_locale())
+            .to_string()
+    }
+}
+
+/// Whether `time` is within the last six months, and not in the future.
+fn is_recent(time: SystemTime, now: SystemTime) -> bool {
+    match now.duration_since(time) {
+        Ok(age) => age < SIX_MONTHS,
+        Err(_) => false,
+    }
+}
+
+/// Checks a user-supplied `strftime` format, translating the GNU `%N` (nanoseconds) into the
+/// chrono equivalent. Formatting with an invalid format would fail at print time otherwise.
+fn to_chrono_format(format: &str) -> Result<String, String> {
+    let format = format.replace("%N", "%f");
+    if StrftimeItems::new(&format).any(|item| matches!(item, Item::Error)) {
+        return Err(format!("invalid time format '{}'", format));
+    }
+    Ok(format)
+}
+
+/// The locale that decides how dates are written: `LC_ALL`, then `LC_TIME`, then `LANG`.
+fn time_locale() -> String {
+    ["LC_ALL", "LC_TIME", "LANG"]
+        .iter()
+        .filter_map(|name| env::var(name).ok())
+        .find(|value| !value.is_empty())
+        .unwrap_or_default()
+}
+
+/// The time locale as chrono knows it, e.g. `de_DE` for `de_DE.UTF-8@euro`. Locales that
+/// chrono has no data for are written like the POSIX locale.
+fn chrono_locale() -> Locale {
+    let locale = time_locale();
+    let name = locale.split(['.', '@']).next().unwrap_or_default();
+    Locale::try_from(name).unwrap_or(Locale::POSIX)
+}
+
+fn is_posix_locale() -> bool {
+    matches!(time_locale().as_str(), "" | "C" | "POSIX")
+}
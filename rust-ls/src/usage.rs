@@ -0,0 +1,237 @@
+use std::{
+    collections::HashMap,
+    fs, io,
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use rayon::prelude::*;
+
+use crate::{block_size::BlockSize, entry::Entry};
+
+/// Sizes of a set of files (`--du`), where a file reached through several hard links only
+/// counts once.
+#[derive(Clone, Debug, Default)]
+pub struct DiskUsage {
+    /// files counted, of every kind
+    pub files: u64,
+    /// hard links that were left out because their file was already counted
+    pub duplicates: u64,
+    /// sizes of the files with a single link, which cannot be met twice
+    apparent: u64,
+    allocated: u64,
+    /// `(dev, ino)` → `(apparent, allocated)` of the files with several links
+    linked: HashMap<(u64, u64), (u64, u64)>,
+}
+
+impl DiskUsage {
+    /// Counts `entry` unless it is another link to a file that was counted before. The implied
+    /// `.` and `..` belong to whoever lists them as a real entry, so they are never counted.
+    pub fn add(&mut self, entry: &Entry) {
+        if entry.is_implied() {
+            return;
+        }
+        // st_blocks is in 512-byte units
+        self.add_file(entry.file_id(), entry.nlink, entry.is_dir(), entry.size, entry.blocks * 512);
+    }
+
+    fn add_file(
+        &mut self,
+        id: (u64, u64),
+        nlink: u64,
+        is_dir: bool,
+        apparent: u64,
+        allocated: u64,
+    ) {
+        if nlink > 1 && !is_dir {
+            if self.linked.insert(id, (apparent, allocated)).is_some() {
+                self.duplicates += 1;
+                return;
+            }
+        } else {
+            self.apparent += apparent;
+            self.allocated += allocated;
+        }
+        self.files += 1;
+    }
+
+    /// Adds the files of `other`, counting those that both have met once.
+    pub fn merge(&mut self, other: DiskUsage) {
+        self.files += other.files;
+        self.duplicates += other.duplicates;
+        self.apparent += other.apparent;
+        self.allocated += other.allocated;
+        for (id, sizes) in other.linked {
+            if self.linked.insert(id, sizes).is_some() {
+                self.files -= 1;
+                self.duplicates += 1;
+            }
+        }
+    }
+
+    /// Sum of the file sizes, in bytes.
+    pub fn apparent(&self) -> u64 {
+        self.apparent
+            + self
+                .linked
+                .values()
+                .map(|(apparent, _)| apparent)
+                .sum::<u64>()
+    }
+
+    /// Sum of the allocated space, in bytes.
+    pub fn allocated(&self) -> u64 {
+        self.allocated
+            + self
+                .linked
+                .values()
+                .map(|(_, allocated)| allocated)
+                .sum::<u64>()
+    }
+
+    /// The summary line with sizes in `unit`, e.g.
+    /// `disk usage: 1.5M apparent, 1.2M allocated in 42 files (3 hard links counted once)`.
+    pub fn summary(&self, unit: &BlockSize) -> String {
+        let files = match self.files {
+            1 => "1 file".to_string(),
+            count => format!("{} files", count),
+        };
+        let mut summary = format!(
+            "disk usage: {} apparent, {} allocated in {}",
+            unit.scale(self.apparent()),
+            unit.scale(self.allocated()),
+            files
+        );
+        match self.duplicates {
+            0 => {},
+            1 => summary.push_str(" (1 hard link counted once)"),
+            count => summary.push_str(&format!(" ({} hard links counted once)", count)),
+        }
+        summary
+    }
+}
+
+/// The recursive size of every directory below a root, read in one walk (`--du`).
+///
+/// Each directory counts a hard-linked file once however often it appears below it, so its
+/// totals are what `du -s` would report for it alone. Symbolic links are not followed.
+#[derive(Debug, Default)]
+pub struct DirectoryTotals {
+    /// directory path, as joined from the root → `(apparent, allocated)` in bytes
+    totals: HashMap<PathBuf, (u64, u64)>,
+    /// what could not be read on the way
+    pub errors: Vec<(PathBuf, io::Error)>,
+}
+
+impl DirectoryTotals {
+    /// Walks the tree below `root` on the rayon thread pool. With `one_file_system`, directories
+    /// on other file systems than the root (mount points) are left out, like `du -x`. Returns the
+    /// usage of the whole tree, root included, along with the totals.
+    pub fn walk(root: &Path, one_file_system: bool) -> io::Result<(DiskUsage, Self)> {
+        let metadata = fs::symlink_metadata(root)?;
+        let walker = Walker {
+            dev: one_file_system.then(|| metadata.dev()),
+            totals: Mutex::default(),
+            errors: Mutex::default(),
+        };
+        let usage = walker.walk(root, &metadata);
+        let mut errors = walker
+            .errors
+            .into_inner()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        // in the order of the paths, not of the threads that met them
+        errors.sort_by(|(a, _), (b, _)| a.cmp(b));
+        let totals = walker
+            .totals
+            .into_inner()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        Ok((usage, DirectoryTotals { totals, errors }))
+    }
+
+    /// Puts the totals of a directory entry in place of its own size and blocks, so that the
+    /// size columns, the `total` line and `-S` all go by what the directory holds. Entries that
+    /// were not walked are left alone.
+    pub fn apply(&self, entry: &mut Entry) {
+        if !entry.is_dir() {
+            return;
+        }
+        if let Some(&(apparent, allocated)) = self.totals.get(&entry.path) {
+            entry.size = apparent;
+            entry.blocks = allocated.div_ceil(512);
+        }
+    }
+}
+
+struct Walker {
+    /// the root's file system, with `--one-file-system`
+    dev: Option<u64>,
+    totals: Mutex<HashMap<PathBuf, (u64, u64)>>,
+    errors: Mutex<Vec<(PathBuf, io::Error)>>,
+}
+
+impl Walker {
+    fn walk(&self, path: &Path, metadata: &fs::Metadata) -> DiskUsage {
+        let mut usage = DiskUsage::default();
+        usage.add_file(
+            (metadata.dev(), metadata.ino()),
+            metadata.nlink(),
+            metadata.is_dir(),
+            metadata.size(),
+            metadata.blocks() * 512,
+        );
+        if !metadata.is_dir() {
+            return usage;
+        }
+
+        let dir_entries = match fs::read_dir(path) {
+            Ok(dir_entries) => dir_entries,
+            Err(err) => {
+                self.error(path, err);
+                return usage;
+            },
+        };
+        let mut children = Vec::new();
+        for dir_entry in dir_entries {
+            match dir_entry {
+                Ok(dir_entry) => children.push(dir_entry),
+                Err(err) => {
+                    self.error(path, err);
+                    break;
+                },
+            }
+        }
+        let children = children
+            .into_par_iter()
+            .filter_map(|dir_entry| {
+                let path = dir_entry.path();
+                match dir_entry.metadata() {
+                    Ok(metadata) if self.dev.is_some_and(|dev| metadata.dev() != dev) => None,
+                    Ok(metadata) => Some(self.walk(&path, &metadata)),
+                    Err(err) => {
+                        self.error(&path, err);
+                        None
+                    },
+                }
+            })
+            .reduce(DiskUsage::default, |mut a, b| {
+                a.merge(b);
+                a
+            });
+        usage.merge(children);
+
+        let mut totals = self
+            .totals
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        totals.insert(path.to_path_buf(), (usage.apparent(), usage.allocated()));
+        usage
+    }
+
+    fn error(&self, path: &Path, err: io::Error) {
+        self.errors
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .push((path.to_path_buf(), err));
+    }
+}
@@ -0,0 +1,110 @@
+use std::{
+    ffi::CString,
+    io,
+    os::{
+        fd::{AsRawFd, FromRawFd, OwnedFd},
+        unix::ffi::OsStrExt,
+    },
+    path::Path,
+    time::Duration,
+};
+
+/// Changes to the entries of a directory that call for a new listing.
+const ENTRY_EVENTS: u32 = libc::IN_CREATE
+    | libc::IN_DELETE
+    | libc::IN_MODIFY
+    | libc::IN_ATTRIB
+    | libc::IN_CLOSE_WRITE
+    | libc::IN_MOVED_FROM
+    | libc::IN_MOVED_TO;
+/// The watched directory itself going away.
+const GONE_EVENTS: u32 = libc::IN_DELETE_SELF | libc::IN_MOVE_SELF | libc::IN_IGNORED;
+
+/// Watches one directory for entries being created, changed or removed, with `inotify(7)`.
+#[derive(Debug)]
+pub struct DirWatcher {
+    fd: OwnedFd,
+}
+
+impl DirWatcher {
+    pub fn new(path: &Path) -> io::Result<Self> {
+        // SAFETY: no pointers are involved; the descriptor is owned right after
+        let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: `fd` is a fresh descriptor that nothing else owns
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        let path = CString::new(path.as_os_str().as_bytes())?;
+        let mask = ENTRY_EVENTS | GONE_EVENTS | libc::IN_ONLYDIR;
+        // SAFETY: `path` is a valid NUL-terminated string
+        if unsafe { libc::inotify_add_watch(fd.as_raw_fd(), path.as_ptr(), mask) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(DirWatcher { fd })
+    }
+
+    /// Blocks until something in the directory changes, then until nothing has changed for
+    /// `settle`, so that a burst of events (e.g. a build writing many files) leads to a single
+    /// redraw. Returns `false` once the directory itself is removed or moved away.
+    pub fn wait(&self, settle: Duration) -> io::Result<bool> {
+        let mut timeout = None;
+        loop {
+            if !self.poll(timeout)? {
+                return Ok(true);
+            }
+            if self.read_events()? & GONE_EVENTS != 0 {
+                return Ok(false);
+            }
+            timeout = Some(settle);
+        }
+    }
+
+    /// Whether events are ready within `timeout`, or whenever they come without one.
+    fn poll(&self, timeout: Option<Duration>) -> io::Result<bool> {
+        let mut pollfd = libc::pollfd { fd: self.fd.as_raw_fd(), events: libc::POLLIN, revents: 0 };
+        let timeout =
+            timeout.map_or(-1, |timeout| timeout.as_millis().min(i32::MAX as u128) as i32);
+        loop {
+            // SAFETY: `pollfd` is a single valid entry
+            match unsafe { libc::poll(&mut pollfd, 1, timeout) } {
+                ready if ready >= 0 => return Ok(ready > 0),
+                _ => {
+                    let err = io::Error::last_os_error();
+                    if err.kind() != io::ErrorKind::Interrupted {
+                        return Err(err);
+                    }
+                },
+            }
+        }
+    }
+
+    /// Reads the pending events, which poll said are there, and returns their masks or'ed
+    /// together. Which entry changed does not matter, since the whole listing is read again.
+    fn read_events(&self) -> io::Result<u32> {
+        // room for many events, each a header followed by a name of at most NAME_MAX bytes
+        let mut buf = [0u8; 16 * 1024];
+        // SAFETY: `buf` has room for `buf.len()` bytes
+        let read = unsafe { libc::read(self.fd.as_raw_fd(), buf.as_mut_ptr().cast(), buf.len()) };
+        if read < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let header = size_of::<libc::inotify_event>();
+        let mut masks = 0;
+        let mut offset = 0;
+        while offset + header <= read as usize {
+            // SAFETY: the kernel writes whole events, and the header lies within what was read
+            let event = unsafe {
+                buf.as_ptr()
+                    .add(offset)
+                    .cast::<libc::inotify_event>()
+                    .read_unaligned()
+            };
+            masks |= event.mask;
+            offset += header + event.len as usize;
+        }
+        Ok(masks)
+    }
+}
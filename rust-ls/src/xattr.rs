@@ -0,0 +1,94 @@
+use std::{
+    ffi::{CString, OsStr, OsString},
+    io,
+    os::unix::ffi::OsStrExt,
+    path::Path,
+};
+
+/// Name of the extended attribute that holds a file's access ACL.
+const ACL_ACCESS: &str = "system.posix_acl_access";
+/// Name of the extended attribute that holds a directory's default ACL.
+const ACL_DEFAULT: &str = "system.posix_acl_default";
+/// Name of the extended attribute that holds a file's SELinux label.
+const SELINUX: &str = "security.selinux";
+
+/// One extended attribute of a file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Xattr {
+    pub name: OsString,
+    pub value: Vec<u8>,
+}
+
+impl Xattr {
+    /// Whether this is one of the attributes that back a POSIX ACL.
+    pub fn is_acl(&self) -> bool {
+        self.name == ACL_ACCESS || self.name == ACL_DEFAULT
+    }
+
+    /// Whether this is the SELinux label, which every file has on an SELinux system.
+    pub fn is_security_context(&self) -> bool {
+        self.name == SELINUX
+    }
+}
+
+/// Reads the extended attributes of `path` without following a final symbolic link, with
+/// `llistxattr(2)` and `lgetxattr(2)`. File systems without extended attributes have none.
+pub fn read_xattrs(path: &Path) -> io::Result<Vec<Xattr>> {
+    let path = CString::new(path.as_os_str().as_bytes())?;
+    let names = read_sized(|buf, size| {
+        // SAFETY: `buf` has room for `size` bytes, or is null when `size` is 0
+        unsafe { libc::llistxattr(path.as_ptr(), buf, size) }
+    })?;
+
+    let mut xattrs = Vec::new();
+    for name in names
+        .split(|&byte| byte == 0)
+        .filter(|name| !name.is_empty())
+    {
+        // an attribute can disappear between the two calls
+        if let Ok(value) = read_value(&path, name) {
+            xattrs.push(Xattr { name: OsStr::from_bytes(name).to_os_string(), value });
+        }
+    }
+
+    // without an active security module, labels can be read but are not listed
+    if !xattrs.iter().any(Xattr::is_security_context)
+        && let Ok(value) = read_value(&path, SELINUX.as_bytes())
+        && !value.is_empty()
+    {
+        xattrs.push(Xattr { name: OsString::from(SELINUX), value });
+    }
+    Ok(xattrs)
+}
+
+fn read_value(path: &CString, name: &[u8]) -> io::Result<Vec<u8>> {
+    let name = CString::new(name)?;
+    read_sized(|buf, size| {
+        // SAFETY: as above, and both strings are NUL-terminated
+        unsafe { libc::lgetxattr(path.as_ptr(), name.as_ptr(), buf.cast(), size) }
+    })
+}
+
+/// Calls a `*xattr` function once for the size and once for the data, retrying if the data grew
+/// in between.
+fn read_sized(mut call: impl FnMut(*mut libc::c_char, usize) -> isize) -> io::Result<Vec<u8>> {
+    loop {
+        let size = call(std::ptr::null_mut(), 0);
+        if size < 0 {
+            let err = io::Error::last_os_error();
+            return match err.raw_os_error() {
+                Some(libc::ENOTSUP) | Some(libc::ENODATA) => Ok(Vec::new()),
+                _ => Err(err),
+            };
+        }
+        let mut buf = vec![0u8; size as usize];
+        let read = call(buf.as_mut_ptr().cast(), buf.len());
+        if read >= 0 {
+            buf.truncate(read as usize);
+            return Ok(buf);
+        }
+        if io::Error::last_os_error().raw_os_error() != Some(libc::ERANGE) {
+            return Err(io::Error::last_os_error());
+        }
+    }
+}